@@ -3,34 +3,384 @@ const DEFAULT_SERVER: &str = "chat.freenode.net";
 const DEFAULT_PORT: &str = "6667";
 const DEFAULT_USERNAME: &str = "minirc_user";
 
-use crate::connection::Connection;
-use argparse::{ArgumentParser, Store};
-use std::io::Result;
+use crate::config::{config_file, Config};
+use crate::connection::{join_address, split_address, Connection};
+use crate::export::{ExportFormat, ExportOptions};
+use crate::import::ImportOptions;
+use crate::settings::{is_valid_time_format, Settings};
+use crate::stats::{ReportFormat, StatsOptions};
+use argparse::{ArgumentParser, Collect, List, Store, StoreFalse, StoreOption, StoreTrue};
+use chrono::NaiveDate;
+use std::io::{stderr, stdout, Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
 
-pub fn setup() -> Result<Connection> {
-    let mut server = String::from(DEFAULT_SERVER);
-    let mut port = String::from(DEFAULT_PORT);
-    let mut passwd = String::new();
-    let mut uname = String::from(DEFAULT_USERNAME);
+/// What minirc was started to do
+#[derive(Debug)]
+pub enum Mode {
+    Client(Box<Connection>), // Connect to a server
+    ImportLogs(ImportOptions),
+    Export(ExportOptions),
+    Stats(StatsOptions),
+}
+
+pub fn setup() -> Result<(Mode, Settings)> {
+    let config_fp = config_file();
+    let config = match config_fp {
+        Some(ref fp) => Config::load(fp)?,
+        None => Config::default(),
+    };
+    let invalid_config = |msg: String| {
+        let fp = config_fp.as_deref().unwrap_or_else(|| Path::new(""));
+        let msg = format!("{}: {}", fp.display(), msg);
+        Error::new(ErrorKind::InvalidInput, msg)
+    };
+
+    let mut network: Option<String> = None;
+    let mut server: Option<String> = None;
+    let mut port: Option<String> = None;
+    let mut passwd: Option<String> = None;
+    let mut uname: Option<String> = None;
+    let mut channels: Vec<String> = Vec::new();
+    let mut backlog: Option<usize> = None;
+    let mut timestamp: Option<String> = None;
+    let mut settings = Settings::default();
+    config.apply(&mut settings).map_err(invalid_config)?;
+    let mut command = String::new();
+    let mut args = Vec::new();
 
     {
         // blocked so borrows go out of scope after parsing
         let mut parser = ArgumentParser::new();
         parser.set_description("Simple IRC client written in Rust.");
-        parser
-            .refer(&mut server)
-            .add_option(&["-s", "--server"], Store, "Server to connect to");
+        parser.refer(&mut network).add_option(
+            &["-N", "--network"],
+            StoreOption,
+            "Network of the configuration file to connect to",
+        );
+        parser.refer(&mut server).add_option(
+            &["-s", "--server"],
+            StoreOption,
+            "Server to connect to",
+        );
         parser
             .refer(&mut port)
-            .add_option(&["-p", "--port"], Store, "Port to connect to");
+            .add_option(&["-p", "--port"], StoreOption, "Port to connect to");
         parser
             .refer(&mut passwd)
-            .add_option(&["-k", "--key"], Store, "Server password");
+            .add_option(&["-k", "--key"], StoreOption, "Server password");
         parser
             .refer(&mut uname)
-            .add_option(&["-n", "--name"], Store, "User handle to use");
+            .add_option(&["-n", "--name"], StoreOption, "User handle to use");
+        parser.refer(&mut channels).add_option(
+            &["-c", "--channel"],
+            Collect,
+            "Channel to join on connecting, as #channel or #channel:key",
+        );
+        parser.refer(&mut settings.history_mode).add_option(
+            &["--history"],
+            Store,
+            "Input history to browse with Up/Down: global or buffer",
+        );
+        parser.refer(&mut backlog).add_option(
+            &["--backlog"],
+            StoreOption,
+            "Number of logged lines to show when opening a buffer",
+        );
+        parser.refer(&mut timestamp).add_option(
+            &["--timestamp"],
+            StoreOption,
+            "strftime-style format of the time shown before each line",
+        );
+        parser.refer(&mut settings.log.format).add_option(
+            &["--log-format"],
+            Store,
+            "Format of the logs to write: text, json or both",
+        );
+        parser.refer(&mut settings.log.path).add_option(
+            &["--log-path"],
+            Store,
+            "Log path template, e.g. {network}/{channel}/{yyyy}-{mm}-{dd}.log",
+        );
+        parser.refer(&mut settings.log.timezone).add_option(
+            &["--log-timezone"],
+            Store,
+            "Timezone whose midnight dated logs rotate at: local, UTC or +HH:MM",
+        );
+        parser
+            .refer(&mut settings.log.compress)
+            .add_option(
+                &["--log-compress"],
+                StoreTrue,
+                "Gzip dated logs once they are rotated",
+            )
+            .add_option(
+                &["--no-log-compress"],
+                StoreFalse,
+                "Don't gzip rotated logs",
+            );
+        parser.refer(&mut settings.log.retention_days).add_option(
+            &["--log-retention"],
+            Store,
+            "Delete dated logs older than this many days, 0 to keep them",
+        );
+        parser
+            .refer(&mut settings.log.sqlite)
+            .add_option(
+                &["--log-sqlite"],
+                StoreTrue,
+                "Also record events in a searchable SQLite database",
+            )
+            .add_option(
+                &["--no-log-sqlite"],
+                StoreFalse,
+                "Don't record events in the SQLite database",
+            );
+        parser.refer(&mut command).add_argument(
+            "command",
+            Store,
+            "Command to run instead of connecting: import-logs, export or stats",
+        );
+        parser
+            .refer(&mut args)
+            .add_argument("args", List, "Arguments of the command");
+        parser.stop_on_first_argument(true);
         parser.parse_args_or_exit();
     }
 
-    Ok(Connection::new(server, port, passwd, uname))
+    // Kept over the configuration file when it is reloaded
+    if let Some(lines) = backlog {
+        settings.backlog_lines = lines;
+        settings.overrides.push("backlog");
+    }
+    if let Some(format) = timestamp {
+        settings.timestamp_format = format;
+        settings.overrides.push("timestamp");
+    }
+
+    if !is_valid_time_format(&settings.timestamp_format) {
+        let msg = format!("Invalid timestamp format: {}", settings.timestamp_format);
+        return Err(Error::new(ErrorKind::InvalidInput, msg));
+    }
+
+    if let Err(msg) = settings.log.validate() {
+        return Err(Error::new(ErrorKind::InvalidInput, msg));
+    }
+
+    let mode = match command.as_str() {
+        "" => {
+            let network = match (network, &server) {
+                // A server alone is connected to without the default network
+                (None, Some(_)) => None,
+                (network, _) => config.network(network.as_deref()).map_err(invalid_config)?,
+            };
+            let mut conn = match network {
+                Some((name, network)) => network
+                    .connection(name, DEFAULT_USERNAME)
+                    .map_err(invalid_config)?,
+                None => Connection::new(
+                    server
+                        .take()
+                        .unwrap_or_else(|| String::from(DEFAULT_SERVER)),
+                    port.take().unwrap_or_else(|| String::from(DEFAULT_PORT)),
+                    String::new(),
+                    String::from(DEFAULT_USERNAME),
+                ),
+            };
+            override_connection(&mut conn, server, port, passwd, uname);
+            for channel in channels {
+                if !conn.autojoin.contains(&channel) {
+                    conn.autojoin.push(channel);
+                }
+            }
+            Mode::Client(Box::new(conn))
+        }
+        "import-logs" => Mode::ImportLogs(import_options(args)),
+        "export" => Mode::Export(export_options(args)?),
+        "stats" => Mode::Stats(stats_options(args)),
+        _ => {
+            let msg = format!("Unknown command: {}", command);
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+    };
+    Ok((mode, settings))
+}
+
+/// Overrides what the configuration says about a connection with the
+/// flags given
+fn override_connection(
+    conn: &mut Connection,
+    server: Option<String>,
+    port: Option<String>,
+    passwd: Option<String>,
+    uname: Option<String>,
+) {
+    match (server, port) {
+        (Some(server), port) => {
+            let port = port.unwrap_or_else(|| String::from(DEFAULT_PORT));
+            conn.addresses = vec![join_address(&server, &port)];
+        }
+        (None, Some(port)) => {
+            for address in conn.addresses.iter_mut() {
+                *address = join_address(split_address(address).0, &port);
+            }
+        }
+        (None, None) => (),
+    }
+    conn.address = conn.addresses[0].clone();
+    if passwd.is_some() {
+        conn.password = passwd;
+    }
+    if let Some(uname) = uname {
+        conn.nick = uname.clone();
+        conn.username = uname;
+    }
+}
+
+/// Parses the arguments of the import-logs command
+fn import_options(mut args: Vec<String>) -> ImportOptions {
+    let mut source = None;
+    let mut network = None;
+    let mut files: Vec<String> = Vec::new();
+
+    {
+        let mut parser = ArgumentParser::new();
+        parser.set_description(
+            "Imports irssi, WeeChat or HexChat logs into minirc's logs. \
+             Buffers are named after the log files as those clients name them.",
+        );
+        parser.refer(&mut source).required().add_option(
+            &["--from"],
+            StoreOption,
+            "Client the logs are from: irssi, weechat or hexchat",
+        );
+        parser.refer(&mut network).add_option(
+            &["--network"],
+            StoreOption,
+            "Server the logs are from, as passed to -s when connecting",
+        );
+        parser
+            .refer(&mut files)
+            .required()
+            .add_argument("files", List, "Log files to import");
+        args.insert(0, String::from("minirc import-logs"));
+        if let Err(code) = parser.parse(args, &mut stdout(), &mut stderr()) {
+            std::process::exit(code);
+        }
+    }
+
+    ImportOptions {
+        source: source.unwrap(),
+        network,
+        files: files.into_iter().map(PathBuf::from).collect(),
+    }
+}
+
+/// Parses the arguments of the export command
+fn export_options(mut args: Vec<String>) -> Result<ExportOptions> {
+    let mut format = ExportFormat::Html;
+    let mut network = String::from(DEFAULT_SERVER);
+    let mut from: Option<String> = None;
+    let mut to: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut buffer = String::new();
+
+    {
+        let mut parser = ArgumentParser::new();
+        parser.set_description("Renders a buffer's log as HTML or Markdown.");
+        parser.refer(&mut format).add_option(
+            &["--format"],
+            Store,
+            "Format to export to: html or markdown",
+        );
+        parser.refer(&mut network).add_option(
+            &["--network"],
+            Store,
+            "Server the buffer is on, as passed to -s when connecting",
+        );
+        parser.refer(&mut from).add_option(
+            &["--from"],
+            StoreOption,
+            "First day to export, as YYYY-MM-DD",
+        );
+        parser.refer(&mut to).add_option(
+            &["--to"],
+            StoreOption,
+            "Last day to export, as YYYY-MM-DD",
+        );
+        parser.refer(&mut output).add_option(
+            &["-o", "--output"],
+            StoreOption,
+            "File to write the export to instead of stdout",
+        );
+        parser.refer(&mut buffer).required().add_argument(
+            "buffer",
+            Store,
+            "Channel or nick to export",
+        );
+        args.insert(0, String::from("minirc export"));
+        if let Err(code) = parser.parse(args, &mut stdout(), &mut stderr()) {
+            std::process::exit(code);
+        }
+    }
+
+    Ok(ExportOptions {
+        format,
+        network,
+        buffer,
+        from: from.as_deref().map(parse_date).transpose()?,
+        to: to.as_deref().map(parse_date).transpose()?,
+        output: output.map(PathBuf::from),
+    })
+}
+
+/// Parses the arguments of the stats command
+fn stats_options(mut args: Vec<String>) -> StatsOptions {
+    let mut format = ReportFormat::Text;
+    let mut output: Option<String> = None;
+    let mut network = String::new();
+    let mut channel = String::new();
+
+    {
+        let mut parser = ArgumentParser::new();
+        parser.set_description("Reports on who talked when and about what in a channel.");
+        parser.refer(&mut format).add_option(
+            &["--format"],
+            Store,
+            "Format of the report: text or html",
+        );
+        parser.refer(&mut output).add_option(
+            &["-o", "--output"],
+            StoreOption,
+            "File to write the report to instead of stdout",
+        );
+        parser.refer(&mut network).required().add_argument(
+            "network",
+            Store,
+            "Server the channel is on, as passed to -s when connecting",
+        );
+        parser.refer(&mut channel).required().add_argument(
+            "channel",
+            Store,
+            "Channel to report on",
+        );
+        args.insert(0, String::from("minirc stats"));
+        if let Err(code) = parser.parse(args, &mut stdout(), &mut stderr()) {
+            std::process::exit(code);
+        }
+    }
+
+    StatsOptions {
+        format,
+        network,
+        channel,
+        output: output.map(PathBuf::from),
+    }
+}
+
+/// Parses a YYYY-MM-DD date
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        let msg = format!("Invalid date, expected YYYY-MM-DD: {}", date);
+        Error::new(ErrorKind::InvalidInput, msg)
+    })
 }
@@ -1,146 +1,204 @@
 #![warn(missing_debug_implementations, rust_2018_idioms)]
-const COMMAND_PREFIX: char = ':';
 const DEBUG_MODE: bool = true;
+const WRITE_POLL: Duration = Duration::from_millis(100);
 
-use pancurses::*;
-use std::io::{prelude::*, BufReader, Result};
-use std::net::{Shutdown, TcpStream};
+use std::io::{prelude::*, Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
 use std::thread;
-
-use libminirc::command::{send_auth, Command};
+use std::time::Duration;
+
+use libminirc::argparse::Mode;
+use libminirc::channel::Event;
+use libminirc::command::{Command, Meta};
+use libminirc::completion::Completer;
+use libminirc::config_dir;
+use libminirc::connection::{CaseMapping, Connection};
+use libminirc::export::export;
+use libminirc::history::History;
+use libminirc::import::import_logs;
 use libminirc::interface::Interface;
+use libminirc::settings::Settings;
+use libminirc::stats::stats;
 use libminirc::thread_tools::*;
 use libminirc::ui::*;
 use libminirc::{argparse, refresh_all};
 
 fn main() -> Result<()> {
-    let conn = argparse::setup()?;
-
-    if let Ok(ref mut stream) = TcpStream::connect(&conn.address) {
-        let term = init_curses(DEBUG_MODE);
-        let (term_rows, term_cols) = term.get_max_yx();
-
-        let buffers_win = term.subwin(1, term_cols, 0, 0).unwrap();
-        let input_win = term.subwin(1, term_cols, term_rows - 1, 0).unwrap();
-        let output_win = term.subwin(term_rows - 2, term_cols, 1, 0).unwrap();
-
-        output_win.printw(format!("Connected to {}\n", &conn.address));
-        refresh_all![buffers_win, input_win, output_win];
-
-        send_auth(&conn, stream)?;
-
-        // Interface clones
-        let interface = Arc::new(Interface::new(conn));
-        let interface_read = interface.clone();
-        let interface_write = interface.clone();
-
-        // Stream clones
-        let stream_read = stream.try_clone().expect("Error cloning stream");
-        let mut stream_write = stream.try_clone().expect("Error cloning stream");
-
-        // Channels
-        let (write_tx, write_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
-        let (stdout_tx, stdout_rx) = mpsc::channel();
-        let stdout_tx_c = stdout_tx.clone();
+    let (mode, settings) = argparse::setup()?;
+    let logs_dir = || {
+        settings
+            .log
+            .dir
+            .clone()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))
+    };
+    match mode {
+        Mode::Client(conn) => run_client(*conn, settings),
+        Mode::ImportLogs(options) => {
+            let count = import_logs(&options, &settings.log, &logs_dir()?)?;
+            println!("Imported {} events", count);
+            Ok(())
+        }
+        Mode::Export(options) => {
+            let casemapping = CaseMapping::default();
+            let rendered = export(&options, &settings.log, &logs_dir()?, casemapping)?;
+            write_output(options.output.as_deref(), &rendered)
+        }
+        Mode::Stats(options) => {
+            let casemapping = CaseMapping::default();
+            let report = stats(&options, &settings.log, &logs_dir()?, casemapping)?;
+            write_output(options.output.as_deref(), &report)
+        }
+    }
+}
 
-        // Set up threads
-        let mut threads = Vec::with_capacity(3);
+/// Writes the output of a command to fp, or to stdout if there is none
+fn write_output(fp: Option<&Path>, output: &str) -> Result<()> {
+    match fp {
+        Some(fp) => std::fs::write(fp, output),
+        None => std::io::stdout().write_all(output.as_bytes()),
+    }
+}
 
-        // Reading incoming data from TcpStream
-        let read_thread = thread::spawn(move || -> Result<()> {
-            let stream = stream_read;
-            let interface = interface_read;
+/// Connects to the server and runs the client until the user quits.
+/// Other networks are connected to with :connect.
+fn run_client(conn: Connection, settings: Settings) -> Result<()> {
+    let history_mode = settings.history_mode;
+    let interface = Arc::new(Interface::new(settings));
+    let addresses = conn.addresses.join(", ");
+    if start_network(&interface, conn).is_err() {
+        println!("Could not connect to {}", addresses);
+        return Ok(());
+    }
 
-            loop {
-                let mut reader = BufReader::new(&stream);
-                let mut message = String::new();
-                reader.read_line(&mut message)?;
-                let command = Command::from(message.as_str());
-                parse_incoming_cmd(command, &interface, &stdout_tx)?;
+    let term = init_curses(DEBUG_MODE);
+    let mut term_size = term.get_max_yx();
+    let (mut buffers_win, mut output_win, mut input_win) = create_windows(&term);
+    refresh_all![buffers_win, input_win, output_win];
 
-                if interface.should_shutdown() {
-                    break;
-                }
-            }
-            Ok(())
-        });
-        threads.push(read_thread);
-
-        // Sending data to TcpStream
-        let write_thread = thread::spawn(move || -> Result<()> {
-            let stream = &mut stream_write;
-            let stdout_tx = stdout_tx_c;
-            let interface = interface_write;
-
-            loop {
-                if interface.should_shutdown() {
-                    break;
-                }
+    let interface_write = interface.clone();
 
-                if let Ok(ref inp) = write_rx.try_recv() {
-                    let argv: Vec<_>;
-                    let active_channel = interface.get_active_channel();
-                    let username = interface.get_username();
-                    let command = if inp.starts_with(COMMAND_PREFIX) {
-                        argv = inp[2..].split_whitespace().collect();
-                        parse_user_cmd(&inp, &interface, &stdout_tx, &argv)
-                    } else {
-                        Command::Privmsg(&username, &active_channel, &inp)
-                    };
-
-                    command.send(stream)?;
-                    if let Some(printable) = command.to_printable() {
-                        if interface.get_active_channel_pos() != 0 {
-                            stdout_tx.send(printable).expect("Could not send to stdout");
-                        }
-                    }
-                }
-            }
-            Ok(())
-        });
-        threads.push(write_thread);
+    // Channels
+    let (write_tx, write_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
 
-        // Main thread -- handling stdout & UI
-        let (output_endy, output_endx) = output_win.get_max_yx();
-        let output_last_line = output_endy - 2;
-        let mut inp = String::new();
-        interface.toggle_refresh_buffers_flag();
+    // Sending data to the active buffer's network
+    let write_thread = thread::spawn(move || -> Result<()> {
+        let interface = interface_write;
 
         loop {
             if interface.should_shutdown() {
                 break;
             }
 
-            refresh_buffers(&buffers_win, &interface);
-
-            if let Ok(printable) = stdout_rx.try_recv() {
-                let max_len = output_endx as usize;
-                let lines = split_line(&printable, max_len);
-                for line in lines {
-                    shift_lines_up(&output_win, output_last_line);
-                    output_win.printw(&line);
-                    output_win.refresh();
+            // Waits for input, checking queued lines and netsplits meanwhile
+            if let Ok(ref inp) = write_rx.recv_timeout(WRITE_POLL) {
+                let argv: Vec<_>;
+                let network = interface.get_active_network();
+                let active_channel = interface.get_active_channel();
+                let nick = interface.get_nick(&network);
+                let command = if inp.starts_with(COMMAND_PREFIX) {
+                    argv = inp[1..].split_whitespace().skip(1).collect();
+                    parse_user_cmd(inp, &interface, &argv)
+                } else if interface.is_active_chat() {
+                    Command::Privmsg(&nick, &active_channel, inp)
+                } else {
+                    interface.print_to_active("Not a channel");
+                    Command::Unknown
+                };
+
+                // What is sent to a channel or query is logged there
+                let meta = Meta::now();
+                let sent = match Event::new(&command, &meta) {
+                    Some(event) if active_channel != network => {
+                        interface.send_to_chan(&network, &active_channel, &command, &event)
+                    }
+                    _ => interface.send(&network, &command),
+                };
+                if let Err(e) = sent {
+                    let printable = format!("{}, see :connect", e);
+                    interface.print_to_active(&printable);
                 }
             }
 
-            inp = handle_input(inp, &input_win, &term, &write_tx);
-            if input_win.is_touched() {
-                input_win.refresh();
+            for (network, line) in interface.take_queued_lines() {
+                // Lines for networks since disconnected from are dropped
+                let _ = interface.send_line(&network, &line);
             }
+            interface.flush_netsplits();
+        }
+        Ok(())
+    });
+
+    // Main thread -- handling stdout & UI
+    let mut inp = String::new();
+    let history_fp = config_dir().map(|dir| dir.join("history"));
+    let mut history = History::new(history_mode, history_fp);
+    let mut completer = Completer::new(COMMAND_PREFIX);
+    let reload_flag = reload_on_sighup()?;
+    interface.set_refresh_buffers_flag();
+
+    loop {
+        if interface.should_shutdown() {
+            break;
+        }
+
+        if reload_flag.swap(false, Ordering::Relaxed) {
+            let reload = format!("{}reload", COMMAND_PREFIX);
+            write_tx.send(reload).expect("Could not send to WRITE");
         }
 
-        for thread in threads {
-            thread.join().unwrap()?;
+        if term.get_max_yx() != term_size {
+            term_size = term.get_max_yx();
+            term.clear();
+            term.refresh();
+            let windows = create_windows(&term);
+            buffers_win = windows.0;
+            output_win = windows.1;
+            input_win = windows.2;
+            redraw_input(&input_win, &inp);
+            interface.set_refresh_buffers_flag();
+            interface.set_refresh_output_flag();
         }
 
-        output_win.printw("Shutting down. Bye!");
-        stream.shutdown(Shutdown::Both)?;
-        endwin();
-    } else {
-        println!("Could not connect to {}", &conn.address);
+        refresh_buffers(&buffers_win, &interface);
+        refresh_output(&output_win, &interface);
+        write_notifications(&interface);
+
+        inp = handle_input(
+            inp,
+            &input_win,
+            &term,
+            &write_tx,
+            &interface,
+            &mut history,
+            &mut completer,
+        );
+        if input_win.is_touched() {
+            input_win.refresh();
+        }
     }
 
+    write_thread.join().unwrap()?;
+
+    output_win.printw("Shutting down. Bye!");
+    interface.disconnect_all();
+    end_curses();
     Ok(())
 }
+
+/// Returns a flag set whenever SIGHUP asks for the configuration to be
+/// reloaded
+#[cfg(unix)]
+fn reload_on_sighup() -> Result<Arc<AtomicBool>> {
+    let flag = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, flag.clone())?;
+    Ok(flag)
+}
+
+#[cfg(not(unix))]
+fn reload_on_sighup() -> Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
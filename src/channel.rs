@@ -1,49 +1,690 @@
-const CONFIG_PATH: &str = ".config/minirc/";
+/// Format of the UTC timestamps in logs
+pub const LOG_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
 
-use std::env;
+/// Name of the SQLite store in the logs directory
+pub const STORE_FILE: &str = "minirc.db";
+
+use crate::command::{action, Command, Meta};
+use crate::connection::CaseMapping;
+use crate::logfile::{file_name, network_name, read_gz_tail, LogConfig};
+use crate::scrollback::{Line, Scroll, Scrollback, Style, View};
+use crate::store::Store;
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::io::{prelude::*, Error, ErrorKind, Result, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text, // Human-readable .txt log
+    Json, // One JSON object per event in a .jsonl log
+    Both,
+}
+
+impl LogFormat {
+    pub fn has_text(self) -> bool {
+        self != Self::Json
+    }
+
+    pub fn has_json(self) -> bool {
+        self != Self::Text
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+/// Something that happened in a channel, with everything that is logged
+/// about it
+#[derive(Debug)]
+pub struct Event<'a> {
+    pub time: DateTime<Utc>,
+    pub kind: &'static str,
+    pub nick: Option<&'a str>,
+    pub hostmask: Option<&'a str>,
+    pub text: &'a str,
+    pub tags: &'a [(&'a str, &'a str)],
+    pub msgid: Option<&'a str>,
+    pub printable: String, // As displayed and written to the text log
+    pub highlight: bool,
+    pub hidden: bool, // Logged but not shown, e.g. as noise
+}
+
+impl<'a> Event<'a> {
+    /// Returns the event for a command, or None if it has nothing to show
+    pub fn new(cmd: &Command<'a>, meta: &'a Meta<'a>) -> Option<Self> {
+        Some(Self {
+            time: meta.time,
+            kind: cmd.kind(),
+            nick: cmd.sender(),
+            hostmask: meta.hostmask,
+            text: cmd.text(),
+            tags: &meta.tags,
+            msgid: meta.msgid(),
+            printable: cmd.to_printable()?,
+            highlight: false,
+            hidden: false,
+        })
+    }
+}
+
+/// An event as written to the JSON log
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonEvent {
+    pub timestamp: String,
+    pub network: String,
+    pub buffer: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub nick: Option<String>,
+    pub hostmask: Option<String>,
+    pub text: String,
+    pub tags: BTreeMap<String, String>,
+    pub msgid: Option<String>,
+}
+
+impl JsonEvent {
+    /// Returns the text of a /me action, if the event is one
+    pub fn action(&self) -> Option<&str> {
+        action(&self.text)
+    }
+
+    /// Returns the event as written to the text log
+    pub fn to_log_line(&self) -> String {
+        format!("{} {}", self.timestamp, self.printable())
+    }
+
+    /// Returns the event as displayed, the way it was when it happened
+    pub fn printable(&self) -> String {
+        let (buffer, text) = (self.buffer.as_str(), self.text.as_str());
+        let nick = self.nick.as_deref();
+        let cmd = match (self.kind.as_str(), nick) {
+            ("privmsg", Some(nick)) => Command::Privmsg(nick, buffer, text),
+            ("notice", _) => Command::Notice(nick.unwrap_or_default(), buffer, text),
+            ("join", Some(nick)) => Command::UserJoin(nick, buffer),
+            ("part", Some(nick)) => Command::UserPart(nick, buffer, text),
+            ("quit", Some(nick)) => Command::UserQuit(nick, text),
+            ("nick", Some(nick)) => Command::UserNick(nick, text),
+            ("topic", Some(nick)) => Command::Topic(nick, buffer, text),
+            _ => Command::Unknown,
+        };
+        cmd.to_printable().unwrap_or_else(|| self.text.clone())
+    }
+}
+
+/// What happened in a channel since it was last shown, least important
+/// first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Activity {
+    None,
+    Events,    // Nick changes, topics and the like
+    Messages,  // Messages and notices
+    Highlight, // Messages highlighting us
+}
+
+impl Activity {
+    /// Returns the activity an event causes
+    pub fn of(event: &Event<'_>) -> Self {
+        match event.kind {
+            _ if event.highlight => Self::Highlight,
+            "privmsg" | "notice" => Self::Messages,
+            _ => Self::Events,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Channel {
     id: String,
     server: String,
+    casemapping: CaseMapping,
+    logs_dir: Option<PathBuf>, // None if logging is disabled
+    store: Option<Arc<Store>>, // Shared with the other logged channels
+    log: LogConfig,
+    date: NaiveDate, // Date of the current log files
     fp: PathBuf,
+    json_fp: PathBuf,
+    nicks: Vec<String>,                    // Most recently active first
+    spoke: HashMap<String, DateTime<Utc>>, // When members last spoke, by folded nick
+    scrollback: Scrollback,
+    activity: Activity,      // Since it was last shown
+    unread: usize,           // Messages since it was last shown
+    log_errors: Vec<String>, // Failures to log, not reported yet
+    log_failing: bool,       // Whether the last write failed to log
+    store_failing: bool,     // Whether the last write failed to be stored
+    scratch: bool,           // Shows results rather than a conversation
 }
 
 impl Channel {
-    pub fn new(id: &str, server: &str) -> Self {
-        let path = match env::var("HOME") {
-            Ok(home) => Path::new(&home)
-                .join(CONFIG_PATH)
-                .join("logs")
-                .join(&server),
-            Err(e) => panic!("Error reading HOME: {}", e),
+    /// Creates a channel, logging to files named after its id folded with
+    /// casemapping. If the logs can't be written, e.g. because HOME isn't
+    /// set, the channel says so and isn't logged.
+    pub fn new(id: &str, server: &str, log: &LogConfig, casemapping: CaseMapping) -> Self {
+        let mut chan = Self {
+            id: id.to_owned(),
+            server: server.to_owned(),
+            casemapping,
+            logs_dir: None,
+            store: None,
+            log: log.clone(),
+            date: log.timezone.today(),
+            fp: PathBuf::new(),
+            json_fp: PathBuf::new(),
+            nicks: Vec::new(),
+            spoke: HashMap::new(),
+            scrollback: Scrollback::new(),
+            activity: Activity::None,
+            unread: 0,
+            log_errors: Vec::new(),
+            log_failing: false,
+            store_failing: false,
+            scratch: false,
+        };
+
+        let opened = match log.dir {
+            Some(ref dir) => chan.open_logs(dir),
+            None => Err(Error::new(ErrorKind::NotFound, "HOME is not set")),
+        };
+        match opened {
+            Ok(()) => chan.tidy_logs(),
+            Err(e) => chan.print(Line::new(&format!("Logging disabled: {}", e), Style::Info)),
+        }
+        chan
+    }
+
+    /// Creates a channel that is never logged, e.g. to show search results
+    pub fn scratch(id: &str, server: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            server: server.to_owned(),
+            casemapping: CaseMapping::default(),
+            logs_dir: None,
+            store: None,
+            log: LogConfig::default(),
+            date: NaiveDate::default(),
+            fp: PathBuf::new(),
+            json_fp: PathBuf::new(),
+            nicks: Vec::new(),
+            spoke: HashMap::new(),
+            scrollback: Scrollback::new(),
+            activity: Activity::None,
+            unread: 0,
+            log_errors: Vec::new(),
+            log_failing: false,
+            store_failing: false,
+            scratch: true,
+        }
+    }
+
+    /// Returns whether the buffer only shows results, like mentions or search
+    pub fn is_scratch(&self) -> bool {
+        self.scratch
+    }
+
+    /// Returns the directory the channel is logged under, if it is logged
+    pub fn get_logs_dir(&self) -> Option<&Path> {
+        self.logs_dir.as_deref()
+    }
+
+    /// Also records the channel's events in store, which its backlog is
+    /// then read from. Channels that aren't logged are left as they are.
+    pub fn set_store(&mut self, store: Arc<Store>) {
+        if self.logs_dir.is_some() && self.log.sqlite {
+            self.store = Some(store);
+        }
+    }
+
+    /// Returns the network and channel names the channel's logs are
+    /// stored under
+    fn log_names(&self) -> (String, String) {
+        (
+            network_name(&self.server),
+            file_name(&self.casemapping.fold(&self.id)),
+        )
+    }
+
+    /// Points the channel at the log files in logs_dir for its current
+    /// date, creating them if needed
+    fn open_logs(&mut self, logs_dir: &Path) -> Result<()> {
+        create_dir_all(logs_dir)?;
+        let logs_dir = logs_dir.canonicalize()?;
+        let (network, channel) = self.log_names();
+        let path = self.log.render(&network, &channel, self.date);
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            let msg = format!("Log path leaves the logs directory: {:?}", path);
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+
+        self.fp = logs_dir.join(path);
+        self.json_fp = self.fp.with_extension("jsonl");
+        if let Some(dir) = self.fp.parent() {
+            create_dir_all(dir)?;
+        }
+        for (enabled, fp) in &[
+            (self.log.format.has_text(), &self.fp),
+            (self.log.format.has_json(), &self.json_fp),
+        ] {
+            if *enabled && !fp.exists() {
+                File::create(fp)?;
+            }
+        }
+        self.logs_dir = Some(logs_dir);
+        Ok(())
+    }
+
+    /// Switches to new log files once the date changed, compressing the
+    /// old ones and deleting expired logs
+    fn rotate(&mut self) -> Result<()> {
+        let today = self.log.timezone.today();
+        let logs_dir = match self.logs_dir {
+            Some(ref dir) if self.log.is_dated() && today != self.date => dir.clone(),
+            _ => return Ok(()),
         };
+        self.date = today;
+        self.open_logs(&logs_dir)?;
+        self.tidy_logs();
+        Ok(())
+    }
 
-        if !path.exists() {
-            create_dir_all(&path).expect("Error creating logs directory");
+    /// Compresses the channel's closed logs and deletes expired ones.
+    /// Failures are kept to be reported, as they don't stop logging.
+    fn tidy_logs(&mut self) {
+        let logs_dir = match self.logs_dir {
+            Some(ref dir) => dir,
+            None => return,
+        };
+        let (network, channel) = self.log_names();
+        let tidied = self
+            .log
+            .compress_closed(logs_dir, &network, &channel, self.date)
+            .and_then(|()| self.log.apply_retention(logs_dir, &network, &channel));
+        if let Err(e) = tidied {
+            let msg = format!("Could not tidy the logs of {}: {}", self.id, e);
+            self.log_errors.push(msg);
         }
+    }
+
+    /// Returns the failures to log or tidy the logs since last asked
+    pub fn take_log_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.log_errors)
+    }
+
+    /// Renames the channel, e.g. a query after the other party changed
+    /// nick, moving its logs and stored events to the new name
+    pub fn rename(&mut self, id: &str) -> Result<()> {
+        self.relocate(id, self.casemapping)
+    }
+
+    /// Changes how the channel's id is folded, moving its logs and stored
+    /// events to the matching names
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) -> Result<()> {
+        let id = self.id.clone();
+        self.relocate(&id, casemapping)
+    }
 
-        let mut fp = path.join(&id);
-        fp.set_extension("txt");
-        if !fp.exists() {
-            File::create(&fp).expect("Error writing buffer file");
+    /// Moves the channel's logs and stored events to where they are kept
+    /// for id folded by casemapping, then switches to both. The channel
+    /// takes them even if moving fails.
+    fn relocate(&mut self, id: &str, casemapping: CaseMapping) -> Result<()> {
+        let logs_dir = match self.logs_dir.clone() {
+            Some(dir) => dir,
+            None => {
+                self.id = id.to_owned();
+                self.casemapping = casemapping;
+                return Ok(());
+            }
+        };
+        let (network, old) = self.log_names();
+        let new = file_name(&casemapping.fold(id));
+        let moved = if new != old {
+            self.log.move_logs(&logs_dir, &network, &old, &new)
+        } else {
+            Ok(())
+        };
+        let stored = match self.store {
+            Some(ref store) => {
+                let old_key = self.casemapping.fold(&self.id);
+                store.rename(&self.server, &old_key, &casemapping.fold(id), id)
+            }
+            None => Ok(()),
+        };
+        self.id = id.to_owned();
+        self.casemapping = casemapping;
+        if moved.is_ok() {
+            self.open_logs(&logs_dir)?;
         }
+        moved.and(stored)
+    }
 
-        let fp = fp.canonicalize().expect("Error resolving file path");
-        let (id, server) = (id.to_owned(), server.to_owned());
-        Self { id, server, fp }
+    /// Returns whether the channel's id is id, ignoring case the way the
+    /// server does
+    pub fn is(&self, id: &str) -> bool {
+        self.casemapping.is_same(&self.id, id)
     }
 
     pub fn get_id(&self) -> &str {
         &self.id
     }
 
-    pub fn write(&mut self, message: &str) -> std::io::Result<()> {
-        let mut file = OpenOptions::new().write(true).append(true).open(&self.fp)?;
-        write!(file, "{}", message)?;
+    pub fn get_server(&self) -> &str {
+        &self.server
+    }
+
+    /// Returns the channel's members, most recently active first
+    pub fn get_nicks(&self) -> &[String] {
+        &self.nicks
+    }
+
+    /// Returns whether nick is a member of the channel
+    pub fn has_nick(&self, nick: &str) -> bool {
+        self.nicks.iter().any(|n| n == nick)
+    }
+
+    /// Adds nick to the end of the member list unless it's already there
+    pub fn add_nick(&mut self, nick: &str) {
+        if !self.has_nick(nick) {
+            self.nicks.push(nick.to_owned());
+        }
+    }
+
+    /// Removes nick from the member list
+    pub fn remove_nick(&mut self, nick: &str) {
+        self.nicks.retain(|n| n != nick);
+    }
+
+    /// Renames a member, keeping its position in the list and when it
+    /// last spoke
+    pub fn rename_nick(&mut self, old: &str, new: &str) {
+        if let Some(n) = self.nicks.iter_mut().find(|n| *n == old) {
+            *n = new.to_owned();
+        }
+        if let Some(time) = self.spoke.remove(&self.casemapping.fold(old)) {
+            self.spoke.insert(self.casemapping.fold(new), time);
+        }
+    }
+
+    /// Returns whether nick spoke in the channel since time
+    pub fn has_spoken_since(&self, nick: &str, time: DateTime<Utc>) -> bool {
+        self.spoke
+            .get(&self.casemapping.fold(nick))
+            .is_some_and(|spoke| *spoke >= time)
+    }
+
+    /// Moves nick to the front of the member list after it spoke
+    pub fn touch_nick(&mut self, nick: &str) {
+        self.remove_nick(nick);
+        self.nicks.insert(0, nick.to_owned());
+    }
+
+    /// Logs an event and adds it to the scrollback. Failures to log or
+    /// store it are kept to be reported, once until it works again.
+    pub fn write(&mut self, event: &Event<'_>) {
+        let style = if event.highlight {
+            Style::Highlight
+        } else {
+            Style::Normal
+        };
+        let line = Line::with_time(Some(event.time), &event.printable, style);
+        let logged = self.log_event(event, &line);
+        if let Some(e) = first_failure(&mut self.log_failing, logged) {
+            let msg = format!("Could not log to {}: {}", self.id, e);
+            self.log_errors.push(msg);
+        }
+        let stored = self.store_event(event);
+        if let Some(e) = first_failure(&mut self.store_failing, stored) {
+            let msg = format!("Could not store the events of {}: {}", self.id, e);
+            self.log_errors.push(msg);
+        }
+        if let ("privmsg", Some(nick)) = (event.kind, event.nick) {
+            self.spoke.insert(self.casemapping.fold(nick), event.time);
+        }
+        if !event.hidden {
+            self.scrollback.push(line);
+        }
+    }
+
+    /// Appends an event, shown as line, to the channel's log files
+    fn log_event(&mut self, event: &Event<'_>, line: &Line) -> Result<()> {
+        if self.logs_dir.is_none() {
+            return Ok(());
+        }
+        self.rotate()?;
+        if self.log.format.has_text() {
+            let mut file = OpenOptions::new().append(true).open(&self.fp)?;
+            writeln!(file, "{}", format_log_line(line))?;
+        }
+        if self.log.format.has_json() {
+            let json = serde_json::to_string(&self.to_json(event))?;
+            let mut file = OpenOptions::new().append(true).open(&self.json_fp)?;
+            writeln!(file, "{}", json)?;
+        }
+        Ok(())
+    }
+
+    /// Adds an event to the store, if the channel's events are stored
+    fn store_event(&self, event: &Event<'_>) -> Result<()> {
+        match self.store {
+            Some(ref store) => store.insert(&self.to_json(event), &self.casemapping.fold(&self.id)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns an event as it is logged in the channel
+    fn to_json(&self, event: &Event<'_>) -> JsonEvent {
+        JsonEvent {
+            timestamp: event.time.format(LOG_TIME_FORMAT).to_string(),
+            network: self.server.clone(),
+            buffer: self.id.clone(),
+            kind: event.kind.to_owned(),
+            nick: event.nick.map(str::to_owned),
+            hostmask: event.hostmask.map(str::to_owned),
+            text: event.text.to_owned(),
+            tags: event
+                .tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            msgid: event.msgid.map(str::to_owned),
+        }
+    }
+
+    /// Shows the last n lines of the channel's log dimmed in the scrollback,
+    /// followed by a separator
+    pub fn load_backlog(&mut self, n: usize) -> Result<()> {
+        if self.logs_dir.is_none() {
+            return Ok(());
+        }
+        let lines = if let Some(ref store) = self.store {
+            let key = self.casemapping.fold(&self.id);
+            let events = store.tail(&self.server, &key, n)?;
+            events.iter().map(JsonEvent::to_log_line).collect()
+        } else if self.log.format.has_text() {
+            self.read_log_tail(&self.fp, n)?
+        } else {
+            let lines = self.read_log_tail(&self.json_fp, n)?;
+            lines.iter().filter_map(|l| json_to_log_line(l)).collect()
+        };
+        if !lines.is_empty() {
+            for line in lines {
+                let (time, text) = parse_log_line(&line);
+                self.scrollback
+                    .push(Line::with_time(time, text, Style::Dim));
+            }
+            self.scrollback
+                .push(Line::new("--- backlog ---", Style::Info));
+        }
         Ok(())
     }
+
+    /// Reads the last n lines of the current log at fp. Right after
+    /// rotating, reads the most recent older log of the same kind instead.
+    fn read_log_tail(&self, fp: &Path, n: usize) -> Result<Vec<String>> {
+        if fp.metadata()?.len() > 0 {
+            return read_tail(fp, n);
+        }
+
+        let logs_dir = self.logs_dir.as_deref().unwrap_or(fp);
+        let (network, channel) = self.log_names();
+        let ext = fp.extension().unwrap_or_default();
+        let previous = self
+            .log
+            .dated_logs(logs_dir, &network, &channel)
+            .into_iter()
+            .rev()
+            .map(|(_, fp)| fp)
+            .find(|prev| {
+                let text_fp = match prev.extension() {
+                    Some(gz) if gz == "gz" => prev.with_extension(""),
+                    _ => prev.clone(),
+                };
+                prev != fp && text_fp.extension().unwrap_or_default() == ext
+            });
+
+        match previous {
+            Some(ref prev) if prev.extension().is_some_and(|e| e == "gz") => read_gz_tail(prev, n),
+            Some(ref prev) => read_tail(prev, n),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Adds a line to the scrollback without logging it
+    pub fn print(&mut self, line: Line) {
+        self.scrollback.push(line);
+    }
+
+    /// Returns what happened since the channel was last shown, and the
+    /// number of messages
+    pub fn get_activity(&self) -> (Activity, usize) {
+        (self.activity, self.unread)
+    }
+
+    /// Notes activity while the channel isn't shown
+    pub fn add_activity(&mut self, activity: Activity) {
+        self.activity = self.activity.max(activity);
+        if activity >= Activity::Messages {
+            self.unread += 1;
+        }
+    }
+
+    /// Forgets the activity, once the channel is shown
+    pub fn clear_activity(&mut self) {
+        self.activity = Activity::None;
+        self.unread = 0;
+    }
+
+    /// Scrolls the scrollback view
+    pub fn scroll(&mut self, scroll: Scroll) {
+        self.scrollback.scroll(scroll);
+    }
+
+    /// Returns whether the scrollback view is scrolled up from its end
+    pub fn is_scrolled(&self) -> bool {
+        self.scrollback.is_scrolled()
+    }
+
+    /// Starts searching the scrollback for lines matching regex, returning
+    /// the number of matches
+    pub fn search(&mut self, regex: Regex) -> usize {
+        self.scrollback.search(regex)
+    }
+
+    /// Scrolls to the next older or newer search match
+    pub fn next_match(&mut self, older: bool) -> Option<(usize, usize)> {
+        self.scrollback.next_match(older)
+    }
+
+    /// Ends searching the scrollback
+    pub fn clear_search(&mut self) {
+        self.scrollback.clear_search();
+    }
+
+    /// Returns the regex the scrollback is being searched for, if any
+    pub fn search_regex(&self) -> Option<&Regex> {
+        self.scrollback.search_regex()
+    }
+
+    /// Returns the scrollback rows to display in a window of the given size
+    pub fn view(&self, width: usize, height: usize, fmt: &str) -> View {
+        self.scrollback.view(width, height, fmt)
+    }
+}
+
+/// Reads the last n lines of the file at fp, reading backwards from its end
+/// so large logs don't have to be read in full
+pub fn read_tail(fp: &Path, n: usize) -> Result<Vec<String>> {
+    const BLOCK_SIZE: u64 = 4096;
+
+    let mut file = File::open(fp)?;
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut pos = len;
+    let mut buf = Vec::new();
+
+    // Stop once the buffer holds n full lines, plus the newline before them
+    while pos > 0 && buf.iter().filter(|&&b| b == b'\n').count() <= n {
+        let read = BLOCK_SIZE.min(pos);
+        pos -= read;
+        let mut block = vec![0; read as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut block)?;
+        block.extend_from_slice(&buf);
+        buf = block;
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let mut lines: Vec<_> = text.lines().collect();
+    if pos > 0 {
+        // The first line may have been cut off
+        lines.remove(0);
+    }
+    let start = lines.len().saturating_sub(n);
+    Ok(lines[start..].iter().map(|l| l.to_string()).collect())
+}
+
+/// Returns a line as written to the log: its UTC time in RFC 3339 format,
+/// followed by a space and its text
+pub fn format_log_line(line: &Line) -> String {
+    let time = line.time.unwrap_or_else(Utc::now);
+    format!("{} {}", time.format(LOG_TIME_FORMAT), line.text)
+}
+
+/// Splits a logged line into its time and text. Lines logged before
+/// timestamps were recorded have no time.
+pub fn parse_log_line(line: &str) -> (Option<DateTime<Utc>>, &str) {
+    let mut split = line.splitn(2, ' ');
+    let time = split.next().unwrap_or_default();
+    match DateTime::parse_from_rfc3339(time) {
+        Ok(time) => (
+            Some(time.with_timezone(&Utc)),
+            split.next().unwrap_or_default(),
+        ),
+        Err(_) => (None, line),
+    }
+}
+
+/// Converts a line of the JSON log to the text log's format
+fn json_to_log_line(line: &str) -> Option<String> {
+    let event: JsonEvent = serde_json::from_str(line).ok()?;
+    Some(event.to_log_line())
+}
+
+/// Notes whether an attempt to log worked, returning its failure unless the
+/// previous attempt failed already
+fn first_failure(failing: &mut bool, result: Result<()>) -> Option<Error> {
+    let was_failing = std::mem::replace(failing, result.is_err());
+    result.err().filter(|_| !was_failing)
 }
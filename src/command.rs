@@ -1,4 +1,8 @@
+/// Longest line a server accepts, without the trailing line break
+const MAX_LINE_LEN: usize = 510;
+
 use crate::connection::Connection;
+use chrono::{DateTime, Utc};
 use std::io::{Result, Write};
 use std::net::TcpStream;
 
@@ -14,6 +18,22 @@ pub enum Command<'msg> {
     Join(&'msg [&'msg str]),                  // Channels
     Part(&'msg [&'msg str]),                  // Channels
     Quit(&'msg str),                          // Quitmsg
+    Cap(&'msg str, &'msg str),                // Subcommand, capabilities
+    UserJoin(&'msg str, &'msg str),           // Nick, channel
+    UserPart(&'msg str, &'msg str, &'msg str), // Nick, channel, reason
+    UserKick(&'msg str, &'msg str, &'msg str, &'msg str), // Kicker, channel, nick, reason
+    UserQuit(&'msg str, &'msg str),           // Nick, reason
+    UserNick(&'msg str, &'msg str),           // Old nick, new nick
+    Topic(&'msg str, &'msg str, &'msg str),   // Setter, channel, topic
+    Names(&'msg str, &'msg str),              // Channel, space separated nicks
+    ISupport(&'msg str),                      // Space separated KEY=value tokens
+    Welcome(&'msg str),                       // Our nick
+    MotdEnd,                                  // End of MOTD, or no MOTD
+    JoinFailed(&'msg str, &'msg str),         // Channel, reason
+    Forward(&'msg str, &'msg str),            // Channel, channel joined instead
+    NickInUse(&'msg str),                     // Our nick, * before registering
+    Authenticate(&'msg str),                  // SASL mechanism or data
+    SaslDone(bool),                           // Whether SASL succeeded
     Unknown,
 }
 
@@ -39,7 +59,10 @@ impl<'msg> Command<'msg> {
                 Some(format!("PART {}\r\n", channels))
             }
             Self::Quit(quitmsg) => Some(format!("QUIT :{}\r\n", quitmsg)),
-            Self::Unknown => None,
+            Self::Cap(subcmd, "") => Some(format!("CAP {}\r\n", subcmd)),
+            Self::Cap(subcmd, caps) => Some(format!("CAP {} :{}\r\n", subcmd, caps)),
+            Self::Authenticate(data) => Some(format!("AUTHENTICATE {}\r\n", data)),
+            _ => None,
         }
     }
 
@@ -53,8 +76,87 @@ impl<'msg> Command<'msg> {
     /// Returns a printable string from a command type.
     pub fn to_printable(&self) -> Option<String> {
         match self {
-            Self::Privmsg(sender, _, msg) => Some(format!("<{}> {}", sender, msg.trim())),
+            Self::Privmsg(sender, _, msg) => Some(match action(msg.trim()) {
+                Some(action) => format!("* {} {}", sender, action),
+                None => format!("<{}> {}", sender, msg.trim()),
+            }),
             Self::Notice(.., msg) => Some(format!("-> {}", msg.trim())),
+            Self::SaslDone(true) => Some(String::from("-- SASL authentication succeeded")),
+            Self::SaslDone(false) => Some(String::from("-- SASL authentication failed")),
+            Self::Topic(setter, _, topic) => {
+                Some(format!("-- {} changed the topic to: {}", setter, topic))
+            }
+            Self::UserJoin(nick, _) => Some(format!("--> {} has joined", nick)),
+            Self::UserPart(nick, _, reason) => Some(format!("<-- {} has left ({})", nick, reason)),
+            Self::UserKick(kicker, _, nick, reason) => Some(format!(
+                "<-- {} was kicked by {} ({})",
+                nick, kicker, reason
+            )),
+            Self::UserQuit(nick, reason) => Some(format!("<-- {} has quit ({})", nick, reason)),
+            Self::UserNick(old, new) => Some(format!("-- {} is now known as {}", old, new)),
+            Self::JoinFailed(channel, reason) => {
+                Some(format!("-- Could not join {}: {}", channel, reason))
+            }
+            Self::Forward(channel, to) => Some(format!("-- {} is forwarded to {}", channel, to)),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of event a command is logged as.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Privmsg(..) => "privmsg",
+            Self::Notice(..) => "notice",
+            Self::UserJoin(..) => "join",
+            Self::UserPart(..) => "part",
+            Self::UserKick(..) => "kick",
+            Self::UserQuit(..) => "quit",
+            Self::UserNick(..) => "nick",
+            Self::Topic(..) => "topic",
+            _ => "other",
+        }
+    }
+
+    /// Returns the nick of the user a command came from, if known.
+    pub fn sender(&self) -> Option<&'msg str> {
+        match self {
+            Self::Privmsg(sender, ..)
+            | Self::Notice(sender, ..)
+            | Self::UserJoin(sender, _)
+            | Self::UserPart(sender, ..)
+            | Self::UserKick(sender, ..)
+            | Self::UserQuit(sender, _)
+            | Self::UserNick(sender, _)
+            | Self::Topic(sender, ..) => Some(sender),
+            _ => None,
+        }
+    }
+
+    /// Returns the free-form text a command carries, such as a message or
+    /// a reason. Returns an empty string if it has none.
+    pub fn text(&self) -> &'msg str {
+        match self {
+            Self::Privmsg(.., msg) | Self::Notice(.., msg) => msg.trim(),
+            Self::UserPart(.., reason) | Self::UserKick(.., reason) => reason,
+            Self::UserQuit(_, reason) => reason,
+            Self::UserNick(_, new) => new,
+            Self::Topic(.., topic) => topic,
+            _ => "",
+        }
+    }
+
+    /// Returns the command a received command has to be answered with.
+    pub fn reply(&self) -> Option<Command<'msg>> {
+        match self {
+            Self::Ping(payload) => Some(Self::Pong(payload)),
+            // Registration waits for capability negotiation to end, and
+            // for SASL to be done if it was asked for
+            Self::Cap("ACK", caps) if caps.split_whitespace().any(|c| c == "sasl") => {
+                Some(Self::Authenticate("PLAIN"))
+            }
+            Self::Cap("ACK", _) | Self::Cap("NAK", _) | Self::SaslDone(_) => {
+                Some(Self::Cap("END", ""))
+            }
             _ => None,
         }
     }
@@ -71,6 +173,11 @@ impl<'msg> Command<'msg> {
 
 impl<'msg> From<&'msg str> for Command<'msg> {
     fn from(inp: &'msg str) -> Self {
+        let inp = strip_tags(inp);
+        if !inp.starts_with(':') {
+            return Self::from_unprefixed(inp);
+        }
+
         let mut split = inp.split_whitespace();
 
         let sender = match split.next() {
@@ -120,16 +227,280 @@ impl<'msg> From<&'msg str> for Command<'msg> {
                 Self::Pong(msg)
             }
 
+            Some("JOIN") => {
+                let channel = split.next().unwrap_or_default();
+                Self::UserJoin(sender, channel.trim_start_matches(':'))
+            }
+
+            Some("PART") => {
+                let channel = split.next().unwrap_or_default();
+                Self::UserPart(sender, channel, trailing(inp, 3))
+            }
+
+            Some("KICK") => {
+                let channel = split.next().unwrap_or_default();
+                let nick = split.next().unwrap_or_default();
+                Self::UserKick(sender, channel, nick, trailing(inp, 4))
+            }
+
+            Some("QUIT") => Self::UserQuit(sender, trailing(inp, 2)),
+
+            Some("NICK") => Self::UserNick(sender, trailing(inp, 2)),
+
+            Some("TOPIC") => {
+                let channel = split.next().unwrap_or_default();
+                Self::Topic(sender, channel, trailing(inp, 3))
+            }
+
+            Some("CAP") => {
+                let subcmd = split.nth(1).unwrap_or_default();
+                Self::Cap(subcmd, trailing(inp, 4))
+            }
+
+            Some("AUTHENTICATE") => Self::Authenticate(trailing(inp, 2)),
+
+            Some("001") => Self::Welcome(split.next().unwrap_or_default()),
+
+            Some("376") | Some("422") => Self::MotdEnd,
+
+            Some("403") | Some("405") | Some("471") | Some("473") | Some("474") | Some("475")
+            | Some("477") => {
+                let channel = split.nth(1).unwrap_or_default();
+                Self::JoinFailed(channel, trailing(inp, 4))
+            }
+
+            Some("470") => {
+                let channel = split.nth(1).unwrap_or_default();
+                Self::Forward(channel, split.next().unwrap_or_default())
+            }
+
+            Some("433") => Self::NickInUse(split.next().unwrap_or_default()),
+
+            Some("903") => Self::SaslDone(true),
+
+            Some("902") | Some("904") | Some("905") | Some("906") => Self::SaslDone(false),
+
+            Some("005") => {
+                let params = inp
+                    .splitn(4, char::is_whitespace)
+                    .nth(3)
+                    .unwrap_or_default();
+                let params = params.split(" :").next().unwrap_or_default();
+                Self::ISupport(params.trim_end())
+            }
+
+            Some("353") => {
+                let channel = split.nth(2).unwrap_or_default();
+                Self::Names(channel, trailing(inp, 5))
+            }
+
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl<'msg> Command<'msg> {
+    /// Parses messages sent without a prefix, which the server we're
+    /// connected to may do for its own messages.
+    fn from_unprefixed(inp: &'msg str) -> Self {
+        match inp.split_whitespace().next() {
+            Some("PING") => {
+                let msg = inp.split_once(' ').map(|(_, msg)| msg);
+                Self::Ping(msg.unwrap_or_default().trim_end())
+            }
+            Some("AUTHENTICATE") => Self::Authenticate(trailing(inp, 1)),
             _ => Self::Unknown,
         }
     }
 }
 
+/// Details of a received message that aren't part of its command
+#[derive(Debug, PartialEq)]
+pub struct Meta<'msg> {
+    pub time: DateTime<Utc>,               // Server time if sent, else now
+    pub hostmask: Option<&'msg str>,       // Full prefix, nick!user@host
+    pub tags: Vec<(&'msg str, &'msg str)>, // IRCv3 message tags
+}
+
+impl<'msg> Meta<'msg> {
+    /// Returns the details of a message sent by us right now
+    pub fn now() -> Self {
+        Self {
+            time: Utc::now(),
+            hostmask: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Returns the value of the message tag key, if present
+    pub fn tag(&self, key: &str) -> Option<&'msg str> {
+        self.tags.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+    }
+
+    /// Returns the IRCv3 message id, if the server sent one
+    pub fn msgid(&self) -> Option<&'msg str> {
+        self.tag("msgid")
+    }
+}
+
+impl<'msg> From<&'msg str> for Meta<'msg> {
+    fn from(inp: &'msg str) -> Self {
+        let tags: Vec<_> = match inp.strip_prefix('@') {
+            Some(tagged) => tagged
+                .split(' ')
+                .next()
+                .unwrap_or_default()
+                .split(';')
+                .map(|tag| {
+                    let mut split = tag.splitn(2, '=');
+                    let key = split.next().unwrap_or_default();
+                    (key, split.next().unwrap_or_default())
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let time = tags
+            .iter()
+            .find(|(key, _)| *key == "time")
+            .and_then(|(_, time)| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now);
+
+        let hostmask = strip_tags(inp)
+            .split(' ')
+            .next()
+            .and_then(|prefix| prefix.strip_prefix(':'));
+
+        Self {
+            time,
+            hostmask,
+            tags,
+        }
+    }
+}
+
+/// Returns a message without its IRCv3 message tags
+fn strip_tags(inp: &str) -> &str {
+    match inp.strip_prefix('@') {
+        Some(tagged) => tagged
+            .split_once(' ')
+            .map(|(_, rest)| rest)
+            .unwrap_or_default(),
+        None => inp,
+    }
+}
+
+/// Returns everything after the first n whitespace separated parts of a
+/// message, without a leading ':' or the trailing line break.
+fn trailing(inp: &str, n: usize) -> &str {
+    let msg = inp
+        .splitn(n + 1, char::is_whitespace)
+        .nth(n)
+        .unwrap_or_default()
+        .trim_end();
+    msg.strip_prefix(':').unwrap_or(msg)
+}
+
 pub fn send_auth(conn: &Connection, stream: &mut TcpStream) -> Result<()> {
+    let caps = match conn.sasl {
+        Some(_) => "server-time sasl",
+        None => "server-time",
+    };
+    Command::Cap("REQ", caps).send(stream)?;
     if let Some(ref passwd) = &conn.password {
         Command::Pass(passwd).send(stream)?;
     }
-    Command::Nick(&conn.username).send(stream)?;
-    Command::User(&conn.username, &conn.username).send(stream)?;
+    Command::Nick(&conn.nick).send(stream)?;
+    Command::User(&conn.username, &conn.realname).send(stream)?;
     Ok(())
 }
+
+/// Splits an autojoin entry, #channel or #channel:key, into the channel
+/// and its key
+pub fn split_key(entry: &str) -> (&str, Option<&str>) {
+    match entry.split_once(':') {
+        Some((channel, key)) => (channel, Some(key)),
+        None => (entry, None),
+    }
+}
+
+/// Returns the JOIN lines joining autojoin entries, with at most
+/// max_targets channels each if the server limits them. Channels with keys
+/// come first, as keys are matched to channels in order.
+pub fn join_lines(entries: &[String], max_targets: Option<usize>) -> Vec<String> {
+    let mut entries: Vec<_> = entries.iter().map(|e| split_key(e)).collect();
+    entries.sort_by_key(|(_, key)| key.is_none());
+
+    let mut lines = Vec::new();
+    let (mut channels, mut keys): (Vec<&str>, Vec<&str>) = (Vec::new(), Vec::new());
+    for (channel, key) in entries {
+        let len = |channels: &[&str], keys: &[&str]| {
+            "JOIN ".len() + channels.join(",").len() + 1 + keys.join(",").len()
+        };
+        let full = max_targets.is_some_and(|max| channels.len() >= max)
+            || len(&channels, &keys) + channel.len() + key.map_or(0, str::len) + 2 > MAX_LINE_LEN;
+        if full && !channels.is_empty() {
+            lines.push(join_line(&channels, &keys));
+            channels.clear();
+            keys.clear();
+        }
+        channels.push(channel);
+        keys.extend(key);
+    }
+    if !channels.is_empty() {
+        lines.push(join_line(&channels, &keys));
+    }
+    lines
+}
+
+/// Returns the JOIN line joining channels, the first ones with keys
+pub fn join_line(channels: &[&str], keys: &[&str]) -> String {
+    match keys {
+        [] => format!("JOIN {}\r\n", channels.join(",")),
+        _ => format!("JOIN {} {}\r\n", channels.join(","), keys.join(",")),
+    }
+}
+
+/// Returns the AUTHENTICATE lines logging in with SASL PLAIN, split into
+/// chunks of 400 bytes as the protocol asks
+pub fn sasl_plain(username: &str, password: &str) -> Vec<String> {
+    let payload = base64(format!("{0}\0{0}\0{1}", username, password).as_bytes());
+    let mut lines: Vec<_> = payload
+        .as_bytes()
+        .chunks(400)
+        .map(|chunk| Command::Authenticate(std::str::from_utf8(chunk).unwrap()))
+        .map(|cmd| cmd.to_unwrapped_string())
+        .collect();
+    // A payload filling its last chunk is ended by an empty one
+    if payload.len().is_multiple_of(400) {
+        lines.push(Command::Authenticate("+").to_unwrapped_string());
+    }
+    lines
+}
+
+/// Returns bytes encoded in standard padded base64
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Returns the text of a CTCP ACTION, the way /me is sent, if msg is one
+pub fn action(msg: &str) -> Option<&str> {
+    let action = msg.strip_prefix("\u{1}ACTION ")?;
+    Some(action.trim_end_matches('\u{1}'))
+}
@@ -0,0 +1,116 @@
+use crate::thread_tools::{resolve_command, COMMANDS};
+
+#[derive(Debug)]
+struct Completion {
+    base: String,            // Line up to the word being completed
+    candidates: Vec<String>, // Replacements for the word, suffix included
+    index: usize,
+}
+
+impl Completion {
+    fn line(&self) -> String {
+        format!("{}{}", self.base, self.candidates[self.index])
+    }
+}
+
+#[derive(Debug)]
+pub struct Completer {
+    prefix: char,
+    state: Option<Completion>,
+}
+
+impl Completer {
+    /// Creates a completer for input lines where commands start with prefix
+    pub fn new(prefix: char) -> Self {
+        Self {
+            prefix,
+            state: None,
+        }
+    }
+
+    /// Forgets the current candidates, so the next call to complete starts
+    /// over. Should be called on every key press that isn't a completion.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Completes the last word of line, returning the completed line.
+    /// Repeated calls cycle through the candidates until reset is called.
+    /// nicks are expected to be ordered most recently active first.
+    pub fn complete(
+        &mut self,
+        line: &str,
+        nicks: &[String],
+        channels: &[String],
+    ) -> Option<String> {
+        if let Some(ref mut state) = self.state {
+            state.index = (state.index + 1) % state.candidates.len();
+            return Some(state.line());
+        }
+
+        let start = line.rfind(' ').map(|i| i + 1).unwrap_or_default();
+        let (base, word) = line.split_at(start);
+        let candidates = self.candidates(base, word, nicks, channels);
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let state = Completion {
+            base: base.to_owned(),
+            candidates,
+            index: 0,
+        };
+        let completed = state.line();
+        self.state = Some(state);
+        Some(completed)
+    }
+
+    fn candidates(
+        &self,
+        base: &str,
+        word: &str,
+        nicks: &[String],
+        channels: &[String],
+    ) -> Vec<String> {
+        // Command names, right after the prefix
+        if base.is_empty() && word.starts_with(self.prefix) {
+            let name = &word[self.prefix.len_utf8()..];
+            return matching(COMMANDS.iter().copied(), name)
+                .map(|cmd| format!("{}{} ", self.prefix, cmd))
+                .collect();
+        }
+
+        let command = if base.starts_with(self.prefix) {
+            base[self.prefix.len_utf8()..]
+                .split_whitespace()
+                .next()
+                .and_then(resolve_command)
+        } else {
+            None
+        };
+        let wants_channel = word.starts_with('#')
+            || word.starts_with('&')
+            || matches!(command, Some("join") | Some("part") | Some("channel"));
+
+        let (candidates, suffix) = if wants_channel {
+            (channels, " ")
+        } else if base.is_empty() {
+            // Nicks at the start of a message get addressed
+            (nicks, ": ")
+        } else {
+            (nicks, " ")
+        };
+        matching(candidates.iter().map(String::as_str), word)
+            .map(|c| format!("{}{}", c, suffix))
+            .collect()
+    }
+}
+
+/// Returns the candidates starting with word, ignoring case
+fn matching<'a>(
+    candidates: impl Iterator<Item = &'a str> + 'a,
+    word: &str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let word = word.to_lowercase();
+    candidates.filter(move |c| c.to_lowercase().starts_with(&word))
+}
@@ -0,0 +1,431 @@
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_PORT: u16 = 6667;
+
+use crate::connection::{join_address, split_address, Connection};
+use crate::highlight::Highlights;
+use crate::ignore::{parse_expiry, Ignore};
+use crate::notify::{parse_quiet_hours, Notifier, NotifyMethod};
+use crate::settings::{is_valid_time_format, Settings};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Contents of the configuration file. Everything is optional, command
+/// line flags override what is set here.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub default_network: Option<String>, // Network connected to without -N
+    pub history: Option<String>,
+    pub backlog: Option<usize>,
+    pub timestamp: Option<String>,
+    pub smart_filter: Option<u64>, // Minutes, 0 to show all joins and such
+    pub log: LogSection,
+    pub highlight: HighlightSection,
+    pub notify: NotifySection,
+    pub ignore: Vec<IgnoreConfig>,
+    pub networks: BTreeMap<String, NetworkConfig>,
+}
+
+/// The [log] section, set like the --log-* flags
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    pub format: Option<String>,
+    pub path: Option<String>,
+    pub timezone: Option<String>,
+    pub compress: Option<bool>,
+    pub retention: Option<u32>,
+    pub sqlite: Option<bool>,
+}
+
+/// The [highlight] section: what makes messages highlight us
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightSection {
+    pub nick: Option<bool>, // Whether our own nick highlights, by default it does
+    pub keywords: Vec<String>, // Words that highlight, ignoring case
+    pub regexes: Vec<String>, // Patterns that highlight, ignoring case
+    pub exclude: Vec<String>, // Channels nothing highlights in
+}
+
+/// The [notify] section: how we are told of highlights and private
+/// messages
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifySection {
+    pub method: Option<String>,      // off, bell, osc777, osc9 or command
+    pub command: Option<String>,     // Run with sh for the command method
+    pub interval: Option<u64>,       // Least seconds between notifications
+    pub quiet_hours: Option<String>, // HH:MM-HH:MM, nothing is notified then
+}
+
+/// An [[ignore]] entry
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IgnoreConfig {
+    pub mask: String,            // nick!user@host glob, or /regex/
+    pub types: Vec<String>,      // messages, notices, ctcp, joins or nicks
+    pub channels: Vec<String>,   // Where it applies, everywhere if empty
+    pub expires: Option<String>, // YYYY-MM-DD or RFC 3339 time
+}
+
+impl IgnoreConfig {
+    /// Returns the ignore, or why it isn't valid
+    pub fn ignore(&self) -> std::result::Result<Ignore, String> {
+        let mut ignore = Ignore::new(&self.mask)?;
+        ignore.types = self
+            .types
+            .iter()
+            .map(|t| t.parse())
+            .collect::<std::result::Result<_, _>>()?;
+        ignore.channels = self.channels.clone();
+        ignore.expires = self.expires.as_deref().map(parse_expiry).transpose()?;
+        ignore.configured = true;
+        Ok(ignore)
+    }
+}
+
+/// A [networks.<name>] section
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub addresses: Vec<String>, // host, host:port or [v6]:port, tried in order
+    pub port: Option<u16>,      // Port of addresses without one
+    pub tls: bool,
+    pub password: Option<String>,
+    pub nick: Option<String>,
+    pub alt_nicks: Vec<String>, // Tried in order if nick is taken
+    pub username: Option<String>,
+    pub realname: Option<String>,
+    pub sasl: Option<SaslConfig>,
+    pub autojoin: Vec<String>,       // #channel or #channel:key
+    pub autojoin_delay: Option<u64>, // Seconds to wait, e.g. for NickServ
+    pub save_autojoin: bool,         // Update autojoin on joining and parting
+    pub on_connect: Vec<String>,     // Raw IRC lines sent once registered
+}
+
+/// Credentials to log in with SASL PLAIN
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SaslConfig {
+    pub username: String,
+    pub password: String,
+}
+
+/// What changed between two versions of the configuration, as far as
+/// the network connected to is concerned
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigDiff {
+    pub applied: Vec<String>,   // Keys applied in place
+    pub join: Vec<String>,      // Channels added to autojoin
+    pub part: Vec<String>,      // Channels removed from autojoin
+    pub reconnect: Vec<String>, // Keys only applied on reconnecting
+    pub kept: Vec<String>,      // Keys left as set on the command line
+}
+
+impl ConfigDiff {
+    /// Notes key as applied in place if it changed and can be, else as
+    /// applied on reconnecting
+    fn changed(&mut self, key: &str, same: bool, live: bool) {
+        match (same, live) {
+            (true, _) => (),
+            (false, true) => self.applied.push(key.to_owned()),
+            (false, false) => self.reconnect.push(key.to_owned()),
+        }
+    }
+
+    /// Moves the keys set on the command line from applied to kept, as
+    /// those win over the file
+    pub fn keep(&mut self, overrides: &[&str]) {
+        let (kept, applied) = self
+            .applied
+            .drain(..)
+            .partition(|key| overrides.contains(&key.as_str()));
+        self.applied = applied;
+        self.kept = kept;
+    }
+}
+
+/// Returns the path of the configuration file, if HOME or
+/// XDG_CONFIG_HOME is set
+pub fn config_file() -> Option<PathBuf> {
+    crate::config_dir().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// Sets the autojoin of network in the configuration file at fp, keeping
+/// the rest of the file as it is
+pub fn save_autojoin(fp: &Path, network: &str, autojoin: &[String]) -> Result<()> {
+    let toml = match read_to_string(fp) {
+        Ok(toml) => toml,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut doc: toml_edit::DocumentMut = toml
+        .parse()
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let item = &mut doc["networks"][network]["autojoin"];
+    let mut autojoin = toml_edit::Value::from(autojoin.iter().collect::<toml_edit::Array>());
+    // Keeps comments after the old list
+    if let Some(old) = item.as_value() {
+        *autojoin.decor_mut() = old.decor().clone();
+    }
+    *item = toml_edit::Item::Value(autojoin);
+    write(fp, doc.to_string())
+}
+
+/// Parses value as a T, naming key in the error
+fn parse_key<T: FromStr<Err = String>>(key: &str, value: &str) -> std::result::Result<T, String> {
+    value.parse().map_err(|e| format!("{}: {}", key, e))
+}
+
+impl Config {
+    /// Parses the contents of a configuration file
+    pub fn parse(toml: &str) -> std::result::Result<Self, String> {
+        toml::from_str(toml).map_err(|e| match e.span() {
+            Some(span) => {
+                let line = toml[..span.start].matches('\n').count() + 1;
+                format!("line {}: {}", line, e.message())
+            }
+            None => e.message().to_owned(),
+        })
+    }
+
+    /// Reads the configuration file at fp, if there is one
+    pub fn load(fp: &Path) -> Result<Self> {
+        let toml = match read_to_string(fp) {
+            Ok(toml) => toml,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        Self::parse(&toml).map_err(|msg| {
+            let msg = format!("{}: {}", fp.display(), msg);
+            Error::new(ErrorKind::InvalidData, msg)
+        })
+    }
+
+    /// Sets the global settings given in the configuration
+    pub fn apply(&self, settings: &mut Settings) -> std::result::Result<(), String> {
+        if let Some(ref history) = self.history {
+            settings.history_mode = parse_key("history", history)?;
+        }
+        if let Some(backlog) = self.backlog {
+            settings.backlog_lines = backlog;
+        }
+        if let Some(ref timestamp) = self.timestamp {
+            if !is_valid_time_format(timestamp) {
+                return Err(format!("timestamp: Invalid format: {}", timestamp));
+            }
+            settings.timestamp_format = timestamp.clone();
+        }
+        if let Some(minutes) = self.smart_filter {
+            settings.smart_filter =
+                Some(Duration::from_secs(minutes * 60)).filter(|d| !d.is_zero());
+        }
+
+        let log = &mut settings.log;
+        if let Some(ref format) = self.log.format {
+            log.format = parse_key("log.format", format)?;
+        }
+        if let Some(ref path) = self.log.path {
+            log.path = path.clone();
+        }
+        if let Some(ref timezone) = self.log.timezone {
+            log.timezone = parse_key("log.timezone", timezone)?;
+        }
+        log.compress = self.log.compress.unwrap_or(log.compress);
+        log.retention_days = self.log.retention.unwrap_or(log.retention_days);
+        log.sqlite = self.log.sqlite.unwrap_or(log.sqlite);
+
+        let highlight = &self.highlight;
+        settings.highlights = Highlights::new(
+            highlight.nick.unwrap_or(true),
+            &highlight.keywords,
+            &highlight.regexes,
+            &highlight.exclude,
+        )
+        .map_err(|e| format!("highlight.regexes: {}", e))?;
+
+        let notify = &self.notify;
+        let method = match (notify.method.as_deref(), &notify.command) {
+            (Some("command"), Some(command)) | (None, Some(command)) => {
+                NotifyMethod::Command(command.clone())
+            }
+            (Some("command"), None) => return Err(String::from("notify.command: Not set")),
+            (Some(method), _) => parse_key("notify.method", method)?,
+            (None, None) => settings.notifier.method.clone(),
+        };
+        let interval = notify
+            .interval
+            .map_or(settings.notifier.interval, Duration::from_secs);
+        let quiet_hours = notify
+            .quiet_hours
+            .as_deref()
+            .map(parse_quiet_hours)
+            .transpose()
+            .map_err(|e| format!("notify.quiet_hours: {}", e))?;
+        settings.notifier = Notifier::new(method, interval, quiet_hours);
+
+        settings.ignores = self
+            .ignore
+            .iter()
+            .map(IgnoreConfig::ignore)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| format!("ignore: {}", e))?;
+        Ok(())
+    }
+
+    /// Returns which settings shared by all networks changed since old
+    pub fn diff(&self, old: &Self) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        let mut changed = |key: &str, same: bool, live: bool| diff.changed(key, same, live);
+        changed("timestamp", self.timestamp == old.timestamp, true);
+        changed("backlog", self.backlog == old.backlog, true);
+        changed("smart_filter", self.smart_filter == old.smart_filter, true);
+        changed("history", self.history == old.history, false);
+        changed("log", self.log == old.log, false);
+        changed("highlight", self.highlight == old.highlight, true);
+        changed("notify", self.notify == old.notify, true);
+        changed("ignore", self.ignore == old.ignore, true);
+        diff
+    }
+
+    /// Returns what changed since old in the configuration of network
+    pub fn diff_network(&self, old: &Self, network: &str) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        let mut changed = |key: &str, same: bool, live: bool| diff.changed(key, same, live);
+        let default = NetworkConfig::default();
+        let new = self.networks.get(network).unwrap_or(&default);
+        let old = old.networks.get(network).unwrap_or(&default);
+        let key = |field: &str| format!("networks.{}.{}", network, field);
+        changed(&key("addresses"), new.addresses == old.addresses, false);
+        changed(&key("port"), new.port == old.port, false);
+        changed(&key("tls"), new.tls == old.tls, false);
+        changed(&key("password"), new.password == old.password, false);
+        changed(&key("nick"), new.nick == old.nick, false);
+        changed(&key("alt_nicks"), new.alt_nicks == old.alt_nicks, false);
+        changed(&key("username"), new.username == old.username, false);
+        changed(&key("realname"), new.realname == old.realname, false);
+        changed(&key("sasl"), new.sasl == old.sasl, false);
+        changed(&key("on_connect"), new.on_connect == old.on_connect, false);
+        changed(
+            &key("autojoin_delay"),
+            new.autojoin_delay == old.autojoin_delay,
+            false,
+        );
+        changed(
+            &key("save_autojoin"),
+            new.save_autojoin == old.save_autojoin,
+            true,
+        );
+
+        let added = new.autojoin.iter().filter(|c| !old.autojoin.contains(c));
+        let removed = old.autojoin.iter().filter(|c| !new.autojoin.contains(c));
+        diff.join = added.cloned().collect();
+        diff.part = removed.cloned().collect();
+        diff
+    }
+
+    /// Returns the network to connect to: the one named, else the default
+    /// one, if any
+    pub fn network(
+        &self,
+        name: Option<&str>,
+    ) -> std::result::Result<Option<(&str, &NetworkConfig)>, String> {
+        let name = match name.or(self.default_network.as_deref()) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        match self.networks.get_key_value(name) {
+            Some((name, network)) => Ok(Some((name, network))),
+            None => Err(format!("No network named {} in the configuration", name)),
+        }
+    }
+
+    /// Returns the connection to target: the network of that name, else
+    /// the server at host[:port]. default_nick is used if no nick is set.
+    pub fn connection_to(
+        &self,
+        target: &str,
+        default_nick: &str,
+    ) -> std::result::Result<Connection, String> {
+        if let Some((name, network)) = self.networks.get_key_value(target) {
+            return network.connection(name, default_nick);
+        }
+        let (host, port) = match split_address(target) {
+            (host, Some(port)) => (host, port.to_owned()),
+            (host, None) => (host, DEFAULT_PORT.to_string()),
+        };
+        if host.is_empty() {
+            return Err(format!("Invalid address: {}", target));
+        }
+        Ok(Connection::new(
+            host.to_owned(),
+            port,
+            String::new(),
+            default_nick.to_owned(),
+        ))
+    }
+}
+
+impl NetworkConfig {
+    /// Returns the connection to the network, logged under name, or why
+    /// it can't be made. default_nick is used if no nick or username is set.
+    pub fn connection(
+        &self,
+        name: &str,
+        default_nick: &str,
+    ) -> std::result::Result<Connection, String> {
+        let key = |field: &str| format!("networks.{}.{}", name, field);
+        if self.addresses.is_empty() {
+            return Err(format!(
+                "{}: At least one address is needed",
+                key("addresses")
+            ));
+        }
+        if self.tls {
+            return Err(format!("{}: TLS connections are not supported", key("tls")));
+        }
+
+        let port = self.port.unwrap_or(DEFAULT_PORT);
+        let mut conn = Connection::new(
+            name.to_owned(),
+            port.to_string(),
+            self.password.clone().unwrap_or_default(),
+            self.username
+                .as_deref()
+                .or(self.nick.as_deref())
+                .unwrap_or(default_nick)
+                .to_owned(),
+        );
+        conn.addresses = self
+            .addresses
+            .iter()
+            .map(|address| match split_address(address) {
+                (host, Some(port)) => join_address(host, port),
+                (host, None) => join_address(host, &port.to_string()),
+            })
+            .collect();
+        conn.address = conn.addresses[0].clone();
+        if let Some(ref nick) = self.nick {
+            conn.nick = nick.clone();
+        }
+        if let Some(ref realname) = self.realname {
+            conn.realname = realname.clone();
+        }
+        conn.alt_nicks = self.alt_nicks.clone();
+        conn.sasl = self
+            .sasl
+            .as_ref()
+            .map(|sasl| (sasl.username.clone(), sasl.password.clone()));
+        conn.autojoin = self.autojoin.clone();
+        conn.autojoin_delay = self.autojoin_delay.unwrap_or_default();
+        conn.save_autojoin = self.save_autojoin;
+        conn.on_connect = self.on_connect.clone();
+        Ok(conn)
+    }
+}
@@ -1,21 +1,187 @@
+/// Channel types of servers that don't announce CHANTYPES
+const DEFAULT_CHANTYPES: &str = "#&";
+
+use std::io::{Error, ErrorKind, Write};
+use std::net::{Shutdown, TcpStream};
+use std::str::FromStr;
+
+/// How the server compares nicks and channel names, as announced by
+/// CASEMAPPING in RPL_ISUPPORT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaseMapping {
+    Ascii,         // Only A-Z are folded
+    Rfc1459,       // Also []\~ are folded to {}|^
+    StrictRfc1459, // Also []\ are folded to {}|
+    Rfc7613,       // Unicode lowercase
+}
+
+impl CaseMapping {
+    /// Returns s folded to lowercase the way the server does
+    pub fn fold(self, s: &str) -> String {
+        if self == Self::Rfc7613 {
+            return s.to_lowercase();
+        }
+        s.chars()
+            .map(|c| match (self, c) {
+                (_, 'A'..='Z') => c.to_ascii_lowercase(),
+                (Self::Rfc1459, '~') => '^',
+                (Self::Rfc1459, '[') | (Self::StrictRfc1459, '[') => '{',
+                (Self::Rfc1459, ']') | (Self::StrictRfc1459, ']') => '}',
+                (Self::Rfc1459, '\\') | (Self::StrictRfc1459, '\\') => '|',
+                _ => c,
+            })
+            .collect()
+    }
+
+    /// Returns whether a and b are the same nick or channel name
+    pub fn is_same(self, a: &str, b: &str) -> bool {
+        self.fold(a) == self.fold(b)
+    }
+}
+
+impl Default for CaseMapping {
+    /// Servers that don't announce a casemapping use RFC 1459's
+    fn default() -> Self {
+        Self::Rfc1459
+    }
+}
+
+impl FromStr for CaseMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Self::Ascii),
+            "rfc1459" => Ok(Self::Rfc1459),
+            "strict-rfc1459" => Ok(Self::StrictRfc1459),
+            "rfc7613" => Ok(Self::Rfc7613),
+            _ => Err(format!("Unknown casemapping: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Connection {
-    pub address: String,
-    pub server: String,
+    pub address: String,        // Address connected to
+    pub addresses: Vec<String>, // Addresses to try, in order
+    pub server: String,         // Name the network's logs are kept under
     pub password: Option<String>,
+    pub nick: String,
+    pub alt_nicks: Vec<String>, // Nicks left to try if nick is taken
     pub username: String,
+    pub realname: String,
+    pub sasl: Option<(String, String)>, // Username and password
+    pub autojoin: Vec<String>,          // #channel or #channel:key
+    pub autojoin_delay: u64,            // Seconds to wait before autojoining
+    pub save_autojoin: bool,            // Whether joins and parts update autojoin
+    pub autojoined: bool,               // Whether autojoin was done
+    pub join_targets: Option<usize>,    // Most channels per JOIN, if limited
+    pub pending_joins: Vec<String>,     // Channels we asked to join
+    pub on_connect: Vec<String>,        // Raw lines sent once registered
+    pub casemapping: CaseMapping,
+    pub chantypes: String,         // Characters channel names start with
+    pub stream: Option<TcpStream>, // Written to, while connected
+    pub session: usize,            // Tells successive connections apart
 }
 
 impl Connection {
     pub fn new(server: String, port: String, password: String, username: String) -> Self {
+        let address = join_address(&server, &port);
         Self {
-            address: format!("{}:{}", server, port),
+            addresses: vec![address.clone()],
+            address,
             server,
             password: match password {
                 p if p.is_empty() => None,
                 p => Some(p),
             },
+            nick: username.clone(),
+            alt_nicks: Vec::new(),
+            realname: username.clone(),
             username,
+            sasl: None,
+            autojoin: Vec::new(),
+            autojoin_delay: 0,
+            save_autojoin: false,
+            autojoined: false,
+            join_targets: None,
+            pending_joins: Vec::new(),
+            on_connect: Vec::new(),
+            casemapping: CaseMapping::default(),
+            chantypes: String::from(DEFAULT_CHANTYPES),
+            stream: None,
+            session: 0,
         }
     }
+
+    /// Connects to the first address that can be reached
+    pub fn connect(&mut self) -> Result<TcpStream, Error> {
+        let mut error = Error::new(ErrorKind::InvalidInput, "No address to connect to");
+        for address in &self.addresses {
+            match TcpStream::connect(address) {
+                Ok(stream) => {
+                    self.address = address.clone();
+                    self.stream = Some(stream.try_clone()?);
+                    return Ok(stream);
+                }
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+
+    /// Sends a raw line to the server
+    pub fn send(&mut self, line: &str) -> Result<(), Error> {
+        match self.stream {
+            Some(ref mut stream) => stream.write_all(line.as_bytes()),
+            None => {
+                let msg = format!("Not connected to {}", self.server);
+                Err(Error::new(ErrorKind::NotConnected, msg))
+            }
+        }
+    }
+
+    /// Closes the connection, if it is open
+    pub fn disconnect(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Moves on to the next nick to try after ours was taken, adding an
+    /// underscore once alternative nicks run out
+    pub fn next_nick(&mut self) -> &str {
+        if self.alt_nicks.is_empty() {
+            self.nick.push('_');
+        } else {
+            self.nick = self.alt_nicks.remove(0);
+        }
+        &self.nick
+    }
+}
+
+/// Splits address into its host and port, if it has one. IPv6 addresses
+/// need brackets to have a port, as in [::1]:6667.
+pub fn split_address(address: &str) -> (&str, Option<&str>) {
+    let is_port = |port: &&str| port.parse::<u16>().is_ok();
+    if let Some((host, rest)) = address
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+    {
+        return (host, rest.strip_prefix(':').filter(is_port));
+    }
+    // IPv6 addresses without brackets have more colons than a port needs
+    match address.split_once(':') {
+        Some((host, port)) if is_port(&port) => (host, Some(port)),
+        _ => (address, None),
+    }
+}
+
+/// Returns the address of port on host, in brackets if it is IPv6
+pub fn join_address(host: &str, port: &str) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
 }
@@ -0,0 +1,415 @@
+/// The 16 colours of mIRC colour codes
+const MIRC_COLOURS: [&str; 16] = [
+    "#ffffff", "#000000", "#00007f", "#009300", "#ff0000", "#7f0000", "#9c009c", "#fc7f00",
+    "#ffff00", "#00fc00", "#009393", "#00ffff", "#0000fc", "#ff00ff", "#7f7f7f", "#d2d2d2",
+];
+
+/// Colours nicks are shown in, picked by a hash of the nick
+const NICK_COLOURS: [&str; 8] = [
+    "#b5372f", "#2f7db5", "#3a9a3a", "#a05eb5", "#c27c0e", "#1e9c96", "#b5457e", "#5b6bbf",
+];
+
+const STYLE: &str = "
+body { font-family: monospace; background: #fdfdfd; color: #222; }
+.line { white-space: pre-wrap; }
+.line:target { background: #fff3b0; }
+.time { color: #888; text-decoration: none; }
+.info { color: #777; }
+.b { font-weight: bold; }
+.i { font-style: italic; }
+.u { text-decoration: underline; }
+";
+
+use crate::channel::{parse_log_line, JsonEvent};
+use crate::connection::CaseMapping;
+use crate::logfile::{file_name, network_name, read_lines, LogConfig};
+use crate::search::is_json_log;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Html,
+    Markdown,
+}
+
+impl ExportFormat {
+    /// Returns the file extension of exports in the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(format!("Unknown export format: {}", s)),
+        }
+    }
+}
+
+/// What to export, as given to the export command
+#[derive(Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub network: String,
+    pub buffer: String,
+    pub from: Option<NaiveDate>, // First day to export, in the log timezone
+    pub to: Option<NaiveDate>,   // Last day to export
+    pub output: Option<PathBuf>, // Where to write the export, else stdout
+}
+
+/// Reads the events of a buffer logged from one day to another, both
+/// included. network and channel are the names its logs are stored under.
+pub fn read_events(
+    log: &LogConfig,
+    logs_dir: &Path,
+    (network, channel): (&str, &str),
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<JsonEvent>> {
+    // JSON logs know more about events than text logs
+    let json = log.format.has_json();
+    let in_range = |date: NaiveDate| from.is_none_or(|f| date >= f) && to.is_none_or(|t| date <= t);
+
+    let logs = if log.is_dated() {
+        log.dated_logs(logs_dir, network, channel)
+            .into_iter()
+            .filter(|(date, fp)| is_json_log(fp) == json && in_range(*date))
+            .map(|(_, fp)| fp)
+            .collect()
+    } else {
+        let fp = logs_dir.join(log.render(network, channel, NaiveDate::default()));
+        vec![if json { fp.with_extension("jsonl") } else { fp }]
+    };
+
+    let mut events = Vec::new();
+    for fp in logs.iter().filter(|fp| fp.exists()) {
+        for line in read_lines(fp)? {
+            let event = if json {
+                serde_json::from_str(&line).ok()
+            } else {
+                text_to_event(&line)
+            };
+            if let Some(event) = event {
+                if event_time(&event).is_some_and(|t| in_range(log.timezone.date(t))) {
+                    events.push(event);
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Reads an event from a line of the text log, as far as it can tell
+fn text_to_event(line: &str) -> Option<JsonEvent> {
+    let (time, text) = parse_log_line(line);
+    let timestamp = time?.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let message = text
+        .strip_prefix('<')
+        .and_then(|t| t.split_once("> "))
+        .map(|(nick, text)| ("privmsg", Some(nick), text.to_owned()));
+    let action = text
+        .strip_prefix("* ")
+        .and_then(|t| t.split_once(' '))
+        .map(|(nick, text)| ("privmsg", Some(nick), format!("\u{1}ACTION {}\u{1}", text)));
+    let topic = text
+        .strip_prefix("-- ")
+        .and_then(|t| t.split_once(" changed the topic to: "))
+        .map(|(nick, topic)| ("topic", Some(nick), topic.to_owned()));
+    let notice = text
+        .strip_prefix("-> ")
+        .map(|notice| ("notice", None, notice.to_owned()));
+    let (kind, nick, text) =
+        message
+            .or(action)
+            .or(topic)
+            .or(notice)
+            .unwrap_or(("other", None, text.to_owned()));
+
+    Some(JsonEvent {
+        timestamp,
+        network: String::new(),
+        buffer: String::new(),
+        kind: kind.to_owned(),
+        nick: nick.map(str::to_owned),
+        hostmask: None,
+        text,
+        tags: BTreeMap::new(),
+        msgid: None,
+    })
+}
+
+/// Returns when an event happened
+pub fn event_time(event: &JsonEvent) -> Option<DateTime<Utc>> {
+    let time = DateTime::parse_from_rfc3339(&event.timestamp).ok()?;
+    Some(time.with_timezone(&Utc))
+}
+
+/// Renders events as a standalone HTML page, with times in the log
+/// timezone and a link target per line
+pub fn render_html(events: &[JsonEvent], title: &str, log: &LogConfig) -> String {
+    let mut style = String::from(STYLE);
+    for (i, colour) in NICK_COLOURS.iter().enumerate() {
+        let _ = writeln!(style, ".n{} {{ color: {}; font-weight: bold; }}", i, colour);
+    }
+    for (i, colour) in MIRC_COLOURS.iter().enumerate() {
+        let _ = writeln!(style, ".f{} {{ color: {}; }}", i, colour);
+        let _ = writeln!(style, ".g{} {{ background: {}; }}", i, colour);
+    }
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(title),
+        style
+    );
+    let mut day = None;
+    for (i, event) in events.iter().enumerate() {
+        let time = match event_time(event) {
+            Some(time) => log.timezone.local_time(time),
+            None => continue,
+        };
+        if day != Some(time.date()) {
+            day = Some(time.date());
+            let _ = writeln!(html, "<h2 id=\"{0}\">{0}</h2>", time.format("%Y-%m-%d"));
+        }
+
+        let nick = event.nick.as_deref().unwrap_or_default();
+        let nick_class = nick.bytes().map(usize::from).sum::<usize>() % NICK_COLOURS.len();
+        let text = match (event.kind.as_str(), event.action()) {
+            ("privmsg", Some(action)) => format!(
+                "* <span class=\"n{}\">{}</span> {}",
+                nick_class,
+                escape_html(nick),
+                format_html(action)
+            ),
+            ("privmsg", None) => format!(
+                "&lt;<span class=\"n{}\">{}</span>&gt; {}",
+                nick_class,
+                escape_html(nick),
+                format_html(&event.text)
+            ),
+            _ => format!(
+                "<span class=\"info\">{}</span>",
+                format_html(&event.printable())
+            ),
+        };
+        let _ = writeln!(
+            html,
+            "<div class=\"line\" id=\"L{0}\"><a class=\"time\" href=\"#L{0}\">{1}</a> {2}</div>",
+            i + 1,
+            time.format("%H:%M:%S"),
+            text
+        );
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Renders events as a Markdown list, with times in the log timezone
+pub fn render_markdown(events: &[JsonEvent], title: &str, log: &LogConfig) -> String {
+    let mut md = format!("# {}\n", escape_markdown(title));
+    let mut day = None;
+    for event in events {
+        let time = match event_time(event) {
+            Some(time) => log.timezone.local_time(time),
+            None => continue,
+        };
+        if day != Some(time.date()) {
+            day = Some(time.date());
+            let _ = writeln!(md, "\n## {}\n", time.format("%Y-%m-%d"));
+        }
+
+        let nick = escape_markdown(event.nick.as_deref().unwrap_or_default());
+        let text = match (event.kind.as_str(), event.action()) {
+            ("privmsg", Some(action)) => {
+                format!(
+                    "\\* **{}** {}",
+                    nick,
+                    escape_markdown(&strip_formatting(action))
+                )
+            }
+            ("privmsg", None) => {
+                format!(
+                    "**{}**: {}",
+                    nick,
+                    escape_markdown(&strip_formatting(&event.text))
+                )
+            }
+            _ => format!(
+                "_{}_",
+                escape_markdown(&strip_formatting(&event.printable()))
+            ),
+        };
+        let _ = writeln!(md, "- `{}` {}", time.format("%H:%M:%S"), text);
+    }
+    md
+}
+
+/// Renders a buffer's log as options ask for, with the buffer name folded
+/// by casemapping as when it was logged
+pub fn export(
+    options: &ExportOptions,
+    log: &LogConfig,
+    logs_dir: &Path,
+    casemapping: CaseMapping,
+) -> Result<String> {
+    let network = network_name(&options.network);
+    let channel = file_name(&casemapping.fold(&options.buffer));
+    let events = read_events(
+        log,
+        logs_dir,
+        (&network, &channel),
+        options.from,
+        options.to,
+    )?;
+
+    let mut title = format!("{} on {}", options.buffer, options.network);
+    match (options.from, options.to) {
+        (Some(from), Some(to)) if from == to => title.push_str(&format!(", {}", from)),
+        (Some(from), Some(to)) => title.push_str(&format!(", {} to {}", from, to)),
+        (Some(from), None) => title.push_str(&format!(", from {}", from)),
+        (None, Some(to)) => title.push_str(&format!(", until {}", to)),
+        (None, None) => (),
+    }
+
+    Ok(match options.format {
+        ExportFormat::Html => render_html(&events, &title, log),
+        ExportFormat::Markdown => render_markdown(&events, &title, log),
+    })
+}
+
+/// Escapes text for use in HTML
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>#|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Reads the digits of an mIRC colour number at the start of s, returning
+/// the colour and the rest of s. 99 is the default colour, returned as none.
+fn take_colour(s: &str) -> Option<(Option<usize>, &str)> {
+    let len = s.chars().take(2).take_while(char::is_ascii_digit).count();
+    let colour = match s[..len].parse::<usize>().ok()? {
+        99 => None,
+        colour => Some(colour % MIRC_COLOURS.len()),
+    };
+    Some((colour, &s[len..]))
+}
+
+/// Formatting set by IRC control codes
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    fg: Option<usize>,
+    bg: Option<usize>,
+}
+
+impl Format {
+    fn classes(self) -> Vec<String> {
+        let mut classes = Vec::new();
+        for (set, class) in &[(self.bold, "b"), (self.italic, "i"), (self.underline, "u")] {
+            if *set {
+                classes.push(class.to_string());
+            }
+        }
+        classes.extend(self.fg.map(|c| format!("f{}", c)));
+        classes.extend(self.bg.map(|c| format!("g{}", c)));
+        classes
+    }
+}
+
+/// Splits text at IRC formatting codes, returning each part with the
+/// formatting it is shown in
+fn split_formatting(text: &str) -> Vec<(Format, String)> {
+    let mut parts = vec![(Format::default(), String::new())];
+    let mut format = Format::default();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\u{2}' => format.bold = !format.bold,
+            '\u{1d}' => format.italic = !format.italic,
+            '\u{1f}' => format.underline = !format.underline,
+            '\u{f}' => format = Format::default(),
+            '\u{3}' => match take_colour(rest) {
+                Some((fg, after)) => {
+                    format.fg = fg;
+                    rest = after;
+                    if let Some((bg, after)) = after.strip_prefix(',').and_then(take_colour) {
+                        format.bg = bg;
+                        rest = after;
+                    }
+                }
+                None => {
+                    format.fg = None;
+                    format.bg = None;
+                }
+            },
+            c if c.is_control() => (),
+            c => {
+                if parts.last().map(|(f, _)| *f) != Some(format) {
+                    parts.push((format, String::new()));
+                }
+                parts.last_mut().unwrap().1.push(c);
+            }
+        }
+    }
+    parts.retain(|(_, s)| !s.is_empty());
+    parts
+}
+
+/// Returns text as HTML with IRC formatting codes turned into styles
+fn format_html(text: &str) -> String {
+    let mut html = String::new();
+    for (format, part) in split_formatting(text) {
+        let classes = format.classes();
+        if classes.is_empty() {
+            html.push_str(&escape_html(&part));
+        } else {
+            let _ = write!(
+                html,
+                "<span class=\"{}\">{}</span>",
+                classes.join(" "),
+                escape_html(&part)
+            );
+        }
+    }
+    html
+}
+
+/// Returns text without IRC formatting codes
+pub fn strip_formatting(text: &str) -> String {
+    split_formatting(text)
+        .into_iter()
+        .map(|(_, part)| part)
+        .collect()
+}
@@ -0,0 +1,75 @@
+/// Characters nicks may contain besides letters and digits, which don't
+/// end a word when looking for a nick
+const NICK_CHARS: &str = "[]\\`_^{|}-";
+
+use crate::connection::CaseMapping;
+use crate::search::build_regex;
+use regex::Regex;
+
+/// What makes a message highlight us
+#[derive(Debug, Clone)]
+pub struct Highlights {
+    pub nick: bool,            // Whether our own nick highlights
+    pub keywords: Vec<String>, // Words that highlight, ignoring case
+    pub regexes: Vec<Regex>,   // Patterns that highlight, ignoring case
+    pub exclude: Vec<String>,  // Channels nothing highlights in
+}
+
+impl Default for Highlights {
+    fn default() -> Self {
+        Self {
+            nick: true,
+            keywords: Vec::new(),
+            regexes: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Highlights {
+    /// Compiles the rules, naming the pattern that isn't a valid regex
+    pub fn new(
+        nick: bool,
+        keywords: &[String],
+        patterns: &[String],
+        exclude: &[String],
+    ) -> Result<Self, String> {
+        let regexes = patterns
+            .iter()
+            .map(|p| build_regex(p, true).map_err(|e| format!("{}: {}", p, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            nick,
+            keywords: keywords.to_vec(),
+            regexes,
+            exclude: exclude.to_vec(),
+        })
+    }
+
+    /// Returns whether text sent to buffer highlights nick. Nicks,
+    /// keywords and buffers are compared with casemapping.
+    pub fn is_match(&self, text: &str, nick: &str, buffer: &str, casemapping: CaseMapping) -> bool {
+        if self.exclude.iter().any(|c| casemapping.is_same(c, buffer)) {
+            return false;
+        }
+        let folded = casemapping.fold(text);
+        let is_word = |word: &str| contains_word(&folded, &casemapping.fold(word));
+        (self.nick && is_word(nick))
+            || self.keywords.iter().any(|k| is_word(k))
+            || self.regexes.iter().any(|r| r.is_match(text))
+    }
+}
+
+/// Returns whether word is found in text, not as part of a longer word or
+/// nick
+fn contains_word(text: &str, word: &str) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+    let is_word_char = |c: char| c.is_alphanumeric() || NICK_CHARS.contains(c);
+    text.match_indices(word).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
@@ -0,0 +1,227 @@
+const HISTORY_SIZE: usize = 1000;
+
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{prelude::*, BufReader, Result};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryMode {
+    Global,    // Up/Down walk through lines sent to any buffer
+    PerBuffer, // Up/Down only walk through lines sent to the active buffer
+}
+
+impl FromStr for HistoryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(Self::Global),
+            "buffer" => Ok(Self::PerBuffer),
+            _ => Err(format!("Unknown history mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Search {
+    query: String,
+    hit: Option<usize>,
+    original: String,
+}
+
+#[derive(Debug)]
+pub struct History {
+    mode: HistoryMode,
+    entries: Vec<(String, String)>, // Buffer, line
+    pos: Option<usize>,
+    draft: String,
+    search: Option<Search>,
+    fp: Option<PathBuf>,
+}
+
+impl History {
+    /// Creates a new history, loading previous entries from fp if given.
+    pub fn new(mode: HistoryMode, fp: Option<PathBuf>) -> Self {
+        let mut history = Self {
+            mode,
+            entries: Vec::new(),
+            pos: None,
+            draft: String::new(),
+            search: None,
+            fp,
+        };
+        // A missing or unreadable history file just means an empty history
+        let _ = history.load();
+        history
+    }
+
+    fn load(&mut self) -> Result<()> {
+        let fp = match self.fp {
+            Some(ref fp) => fp,
+            None => return Ok(()),
+        };
+
+        let file = File::open(fp)?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut split = line.splitn(2, '\t');
+            if let (Some(buffer), Some(line)) = (split.next(), split.next()) {
+                self.entries.push((buffer.to_owned(), line.to_owned()));
+            }
+        }
+
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.drain(..self.entries.len() - HISTORY_SIZE);
+            let mut file = File::create(fp)?;
+            for (buffer, line) in &self.entries {
+                writeln!(file, "{}\t{}", buffer, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, buffer: &str, line: &str) -> Result<()> {
+        if let Some(ref fp) = self.fp {
+            if let Some(dir) = fp.parent() {
+                create_dir_all(dir)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(fp)?;
+            writeln!(file, "{}\t{}", buffer, line)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the entry at position i belongs to buffer's history
+    fn is_visible(&self, i: usize, buffer: &str) -> bool {
+        self.mode == HistoryMode::Global || self.entries[i].0 == buffer
+    }
+
+    /// Adds a sent line to the history and stops browsing
+    pub fn push(&mut self, buffer: &str, line: &str) -> Result<()> {
+        self.reset();
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        self.entries.push((buffer.to_owned(), line.to_owned()));
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.remove(0);
+        }
+        self.save(buffer, line)
+    }
+
+    /// Stops browsing the history, forgetting the saved draft
+    pub fn reset(&mut self) {
+        self.pos = None;
+        self.draft.clear();
+        self.search = None;
+    }
+
+    /// Returns the previous (older) line, saving current as draft when
+    /// browsing starts
+    pub fn prev(&mut self, buffer: &str, current: &str) -> Option<&str> {
+        let end = match self.pos {
+            Some(pos) => pos,
+            None => self.entries.len(),
+        };
+        let found = (0..end).rev().find(|&i| self.is_visible(i, buffer))?;
+        if self.pos.is_none() {
+            self.draft = current.to_owned();
+        }
+        self.pos = Some(found);
+        Some(&self.entries[found].1)
+    }
+
+    /// Returns the next (newer) line, or the saved draft once the newest
+    /// line has been passed
+    pub fn next(&mut self, buffer: &str) -> Option<&str> {
+        let start = self.pos? + 1;
+        match (start..self.entries.len()).find(|&i| self.is_visible(i, buffer)) {
+            Some(found) => {
+                self.pos = Some(found);
+                Some(&self.entries[found].1)
+            }
+            None => {
+                self.pos = None;
+                Some(&self.draft)
+            }
+        }
+    }
+
+    /// Returns whether an incremental reverse search is running
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Starts an incremental reverse search, or jumps to the next older
+    /// match if one is already running
+    pub fn search_start(&mut self, buffer: &str, current: &str) {
+        match self.search.take() {
+            Some(mut search) => {
+                let end = search.hit.unwrap_or(self.entries.len());
+                if let Some(hit) = self.find(buffer, &search.query, end) {
+                    search.hit = Some(hit);
+                }
+                self.search = Some(search);
+            }
+            None => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    hit: None,
+                    original: current.to_owned(),
+                });
+            }
+        }
+    }
+
+    /// Appends c to the search query and searches again from the newest line
+    pub fn search_push(&mut self, buffer: &str, c: char) {
+        if let Some(mut search) = self.search.take() {
+            search.query.push(c);
+            search.hit = self.find(buffer, &search.query, self.entries.len());
+            self.search = Some(search);
+        }
+    }
+
+    /// Removes the last character from the search query
+    pub fn search_pop(&mut self, buffer: &str) {
+        if let Some(mut search) = self.search.take() {
+            search.query.pop();
+            search.hit = if search.query.is_empty() {
+                None
+            } else {
+                self.find(buffer, &search.query, self.entries.len())
+            };
+            self.search = Some(search);
+        }
+    }
+
+    /// Ends the search, returning the matched line (or the line that was
+    /// being edited before the search if accept is false or nothing matched)
+    pub fn search_end(&mut self, accept: bool) -> String {
+        match self.search.take() {
+            Some(Search { hit: Some(hit), .. }) if accept => self.entries[hit].1.clone(),
+            Some(search) => search.original,
+            None => String::new(),
+        }
+    }
+
+    /// Returns the prompt shown while searching, e.g. (reverse-i-search)`foo': foobar
+    pub fn search_prompt(&self) -> Option<String> {
+        self.search.as_ref().map(|search| {
+            let hit = search.hit.map(|i| self.entries[i].1.as_str());
+            format!(
+                "(reverse-i-search)`{}': {}",
+                search.query,
+                hit.unwrap_or_default()
+            )
+        })
+    }
+
+    /// Finds the newest visible entry before end containing query
+    fn find(&self, buffer: &str, query: &str, end: usize) -> Option<usize> {
+        (0..end)
+            .rev()
+            .find(|&i| self.is_visible(i, buffer) && self.entries[i].1.contains(query))
+    }
+}
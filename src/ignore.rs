@@ -0,0 +1,177 @@
+use crate::command::Command;
+use crate::connection::CaseMapping;
+use crate::search::build_regex;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use regex::Regex;
+use std::str::FromStr;
+
+/// Kinds of events an ignore applies to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IgnoreType {
+    Messages, // Messages and actions
+    Notices,
+    Ctcp,  // CTCP requests and replies
+    Joins, // Joins, parts, kicks and quits
+    Nicks, // Nick changes
+}
+
+impl IgnoreType {
+    /// Returns the kind of event cmd is, and the channel it happened in
+    /// if any. Returns None if it can't be ignored.
+    pub fn of<'a>(cmd: &Command<'a>) -> Option<(Self, Option<&'a str>)> {
+        let is_ctcp = |text: &str| text.starts_with('\u{1}') && !text.starts_with("\u{1}ACTION");
+        match *cmd {
+            Command::Privmsg(_, target, text) | Command::Notice(_, target, text)
+                if is_ctcp(text) =>
+            {
+                Some((Self::Ctcp, Some(target)))
+            }
+            Command::Privmsg(_, target, _) => Some((Self::Messages, Some(target))),
+            Command::Notice(_, target, _) => Some((Self::Notices, Some(target))),
+            Command::UserJoin(_, channel)
+            | Command::UserPart(_, channel, _)
+            | Command::UserKick(_, channel, ..) => Some((Self::Joins, Some(channel))),
+            Command::UserQuit(..) => Some((Self::Joins, None)),
+            Command::UserNick(..) => Some((Self::Nicks, None)),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Messages => "messages",
+            Self::Notices => "notices",
+            Self::Ctcp => "ctcp",
+            Self::Joins => "joins",
+            Self::Nicks => "nicks",
+        }
+    }
+}
+
+impl FromStr for IgnoreType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "messages" => Ok(Self::Messages),
+            "notices" => Ok(Self::Notices),
+            "ctcp" => Ok(Self::Ctcp),
+            "joins" => Ok(Self::Joins),
+            "nicks" => Ok(Self::Nicks),
+            _ => Err(format!("Unknown ignore type: {}", s)),
+        }
+    }
+}
+
+/// Events of senders matching a mask that aren't shown or logged
+#[derive(Debug, Clone)]
+pub struct Ignore {
+    pub mask: String, // nick!user@host glob, or /regex/
+    regex: Regex,
+    pub types: Vec<IgnoreType>,         // Everything if empty
+    pub channels: Vec<String>,          // Everywhere if empty
+    pub expires: Option<DateTime<Utc>>, // Forever if None
+    pub configured: bool,               // Whether it comes from the configuration
+}
+
+impl Ignore {
+    /// Creates an ignore of mask, which is a regex if it is between
+    /// slashes, else a glob of * and ?. A mask without ! or @ is a nick.
+    pub fn new(mask: &str) -> Result<Self, String> {
+        let pattern = match mask.strip_prefix('/').and_then(|m| m.strip_suffix('/')) {
+            Some(regex) => regex.to_owned(),
+            None if mask.contains(['!', '@']) => glob_to_regex(mask),
+            None => glob_to_regex(&format!("{}!*@*", mask)),
+        };
+        let regex = build_regex(&pattern, true).map_err(|e| format!("{}: {}", mask, e))?;
+        Ok(Self {
+            mask: mask.to_owned(),
+            regex,
+            types: Vec::new(),
+            channels: Vec::new(),
+            expires: None,
+            configured: false,
+        })
+    }
+
+    /// Returns whether a command sent from hostmask is ignored
+    pub fn is_match(&self, cmd: &Command<'_>, hostmask: &str, casemapping: CaseMapping) -> bool {
+        let (kind, channel) = match IgnoreType::of(cmd) {
+            Some(of) => of,
+            None => return false,
+        };
+        let in_channel = match channel {
+            _ if self.channels.is_empty() => true,
+            Some(channel) => self
+                .channels
+                .iter()
+                .any(|c| casemapping.is_same(c, channel)),
+            // Quits and nick changes are in no channel in particular
+            None => false,
+        };
+        (self.types.is_empty() || self.types.contains(&kind))
+            && in_channel
+            && self.regex.is_match(hostmask)
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Returns the ignore as listed
+    pub fn describe(&self) -> String {
+        let mut s = self.mask.clone();
+        if !self.types.is_empty() {
+            let types: Vec<_> = self.types.iter().map(|t| t.name()).collect();
+            s.push_str(&format!(" ({})", types.join(", ")));
+        }
+        if !self.channels.is_empty() {
+            s.push_str(&format!(" in {}", self.channels.join(", ")));
+        }
+        if let Some(expires) = self.expires {
+            s.push_str(&format!(" until {}", expires.format("%Y-%m-%d %H:%M UTC")));
+        }
+        s
+    }
+}
+
+/// Returns an anchored regex matching what glob matches
+fn glob_to_regex(glob: &str) -> String {
+    let pattern: String = glob
+        .split('*')
+        .map(|part| {
+            let parts: Vec<_> = part.split('?').map(regex::escape).collect();
+            parts.join(".")
+        })
+        .collect::<Vec<_>>()
+        .join(".*");
+    format!("^{}$", pattern)
+}
+
+/// Parses how long an ignore lasts, as a number of minutes, hours or days
+/// such as 30m, 2h or 7d
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration, expected e.g. 30m, 2h or 7d: {}", s);
+    let unit = s.chars().next_back().ok_or_else(invalid)?;
+    let n: i64 = match s[..s.len() - unit.len_utf8()].parse() {
+        Ok(n) if n > 0 => n,
+        _ => return Err(invalid()),
+    };
+    match unit {
+        'm' => Ok(Duration::minutes(n)),
+        'h' => Ok(Duration::hours(n)),
+        'd' => Ok(Duration::days(n)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses when an ignore expires, as an RFC 3339 time or a date, taken as
+/// its start in UTC
+pub fn parse_expiry(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| format!("Invalid time, expected YYYY-MM-DD or RFC 3339: {}", s))
+}
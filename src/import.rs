@@ -0,0 +1,385 @@
+use crate::channel::{JsonEvent, LOG_TIME_FORMAT, STORE_FILE};
+use crate::connection::CaseMapping;
+use crate::logfile::{file_name, merge_log, network_name, LogConfig};
+use crate::store::Store;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A client whose logs can be imported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogSource {
+    Irssi,
+    Weechat,
+    Hexchat,
+}
+
+impl FromStr for LogSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "irssi" => Ok(Self::Irssi),
+            "weechat" => Ok(Self::Weechat),
+            "hexchat" => Ok(Self::Hexchat),
+            _ => Err(format!("Unknown log source: {}", s)),
+        }
+    }
+}
+
+/// What to import, as given to the import-logs subcommand
+#[derive(Debug)]
+pub struct ImportOptions {
+    pub source: LogSource,
+    pub network: Option<String>, // Server the logs are from, else guessed from their paths
+    pub files: Vec<PathBuf>,
+}
+
+/// An event read from another client's log, in the local time it was
+/// logged in
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub time: NaiveDateTime,
+    pub kind: &'static str,
+    pub nick: Option<String>,
+    pub hostmask: Option<String>,
+    pub text: String,
+}
+
+impl LogEntry {
+    fn new(time: NaiveDateTime, kind: &'static str, nick: Option<&str>, text: &str) -> Self {
+        Self {
+            time,
+            kind,
+            nick: nick.map(str::to_owned),
+            hostmask: None,
+            text: text.to_owned(),
+        }
+    }
+}
+
+/// Parses the lines of other clients' logs that look the same in all of
+/// them: joins, parts, quits and nick changes
+struct Membership {
+    join: Regex,
+    part: Regex,
+    quit: Regex,
+    nick: Regex,
+}
+
+impl Membership {
+    fn new() -> Self {
+        // irssi puts hostmasks and reasons in [], WeeChat and HexChat in ()
+        let re = |s: &str| Regex::new(s).unwrap();
+        Self {
+            join: re(r"^(\S+) [(\[](\S+)[)\]] has joined \S+$"),
+            part: re(r"^(\S+) [(\[](\S+)[)\]] has left \S+(?: [(\[](.*)[)\]])?$"),
+            quit: re(r"^(\S+) (?:[(\[](\S+)[)\]] )?has quit(?: [(\[](.*)[)\]])?$"),
+            nick: re(r"^(\S+) is now known as (\S+)$"),
+        }
+    }
+
+    fn parse(&self, time: NaiveDateTime, text: &str) -> Option<LogEntry> {
+        let (kind, caps) = if let Some(caps) = self.join.captures(text) {
+            ("join", caps)
+        } else if let Some(caps) = self.part.captures(text) {
+            ("part", caps)
+        } else if let Some(caps) = self.quit.captures(text) {
+            ("quit", caps)
+        } else {
+            let caps = self.nick.captures(text)?;
+            return Some(LogEntry::new(time, "nick", Some(&caps[1]), &caps[2]));
+        };
+
+        let nick = &caps[1];
+        let reason = caps.get(3).map_or("", |m| m.as_str());
+        let mut entry = LogEntry::new(time, kind, Some(nick), reason);
+        entry.hostmask = caps.get(2).map(|m| format!("{}!{}", nick, m.as_str()));
+        Some(entry)
+    }
+}
+
+/// Returns nick without the channel privilege prefix clients show it with
+fn strip_mode(nick: &str) -> &str {
+    nick.trim_start_matches(|c| "~&@%+ ".contains(c))
+}
+
+/// Returns a CTCP ACTION, the way /me is sent, with text
+fn action(text: &str) -> String {
+    format!("\u{1}ACTION {}\u{1}", text)
+}
+
+/// Parses an irssi log
+pub fn parse_irssi(log: &str) -> Vec<LogEntry> {
+    let membership = Membership::new();
+    let message = Regex::new(r"^<([^>]+)> ?(.*)$").unwrap();
+    let notice = Regex::new(r"^-([^-(:]+)[^-]*- ?(.*)$").unwrap();
+    let mut date = None;
+    let mut entries = Vec::new();
+
+    for line in log.lines() {
+        if let Some(opened) = line.strip_prefix("--- Log opened ") {
+            date = parse_date_time(opened).map(|t| t.date()).or(date);
+            continue;
+        }
+        if let Some(changed) = line.strip_prefix("--- Day changed ") {
+            date = NaiveDate::parse_from_str(&squeeze(changed), "%a %b %d %Y")
+                .ok()
+                .or(date);
+            continue;
+        }
+
+        let (stamp, rest) = match (date, line.split_once(' ')) {
+            (Some(date), Some((stamp, rest))) => match parse_time(stamp) {
+                Some(time) => (date.and_time(time), rest),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        let entry = if let Some(caps) = message.captures(rest) {
+            LogEntry::new(stamp, "privmsg", Some(strip_mode(&caps[1])), &caps[2])
+        } else if let Some(me) = rest.strip_prefix(" * ") {
+            let (nick, text) = me.split_once(' ').unwrap_or((me, ""));
+            LogEntry::new(stamp, "privmsg", Some(nick), &action(text))
+        } else if let Some(info) = rest.strip_prefix("-!- ") {
+            membership
+                .parse(stamp, info)
+                .unwrap_or_else(|| LogEntry::new(stamp, "other", None, info))
+        } else if let Some(caps) = notice.captures(rest) {
+            LogEntry::new(stamp, "notice", Some(&caps[1]), &caps[2])
+        } else {
+            LogEntry::new(stamp, "other", None, rest)
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Parses a WeeChat log
+pub fn parse_weechat(log: &str) -> Vec<LogEntry> {
+    let membership = Membership::new();
+    let mut entries = Vec::new();
+
+    for line in log.lines() {
+        let mut split = line.splitn(3, '\t');
+        let (stamp, prefix, text) = match (split.next(), split.next(), split.next()) {
+            (Some(stamp), Some(prefix), Some(text)) => (stamp, prefix, text),
+            _ => continue,
+        };
+        let stamp = match NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M:%S") {
+            Ok(stamp) => stamp,
+            Err(_) => continue,
+        };
+
+        let entry = match prefix.trim() {
+            "-->" | "<--" | "--" => membership
+                .parse(stamp, text)
+                .unwrap_or_else(|| LogEntry::new(stamp, "other", None, text)),
+            "*" => {
+                let (nick, text) = text.split_once(' ').unwrap_or((text, ""));
+                LogEntry::new(stamp, "privmsg", Some(nick), &action(text))
+            }
+            "" => LogEntry::new(stamp, "other", None, text),
+            nick => LogEntry::new(stamp, "privmsg", Some(strip_mode(nick)), text),
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Parses a HexChat log
+pub fn parse_hexchat(log: &str) -> Vec<LogEntry> {
+    let membership = Membership::new();
+    let mut year = None;
+    let mut last_month = 0;
+    let mut entries = Vec::new();
+
+    for line in log.lines() {
+        if let Some(begin) = line.strip_prefix("**** BEGIN LOGGING AT ") {
+            if let Some(time) = parse_date_time(begin) {
+                year = Some(time.year());
+                last_month = time.month();
+            }
+            continue;
+        }
+
+        // Lines start with a timestamp like "Jun 01 12:30:05"
+        let mut split = line.split(' ').filter(|s| !s.is_empty());
+        let (month, day, time) = match (split.next(), split.next(), split.next()) {
+            (Some(month), Some(day), Some(time)) => (month, day, time),
+            _ => continue,
+        };
+        let rest = match line.split_once(time) {
+            Some((_, rest)) => rest.trim_start_matches(' '),
+            None => continue,
+        };
+        let month = match NaiveDate::parse_from_str(&format!("{} 1 2000", month), "%b %d %Y") {
+            Ok(date) => date.month(),
+            Err(_) => continue,
+        };
+        // Logs only have the year when logging began
+        let year = match year {
+            Some(ref mut year) => {
+                if month < last_month {
+                    *year += 1;
+                }
+                last_month = month;
+                *year
+            }
+            None => continue,
+        };
+        let stamp = match (
+            day.parse()
+                .ok()
+                .and_then(|d| NaiveDate::from_ymd_opt(year, month, d)),
+            parse_time(time),
+        ) {
+            (Some(date), Some(time)) => date.and_time(time),
+            _ => continue,
+        };
+
+        let (prefix, text) = rest.split_once('\t').unwrap_or(("", rest));
+        let entry = if prefix == "*" {
+            membership.parse(stamp, text).unwrap_or_else(|| {
+                let (nick, text) = text.split_once(' ').unwrap_or((text, ""));
+                LogEntry::new(stamp, "privmsg", Some(nick), &action(text))
+            })
+        } else if prefix.starts_with('<') && prefix.ends_with('>') {
+            let nick = strip_mode(&prefix[1..prefix.len() - 1]);
+            LogEntry::new(stamp, "privmsg", Some(nick), text)
+        } else if prefix.len() > 2 && prefix.starts_with('-') && prefix.ends_with('-') {
+            LogEntry::new(stamp, "notice", Some(&prefix[1..prefix.len() - 1]), text)
+        } else {
+            LogEntry::new(stamp, "other", None, rest.trim())
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Replaces runs of whitespace in s by single spaces
+fn squeeze(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parses a date like "Mon Jun 01 12:30:05 2020"
+fn parse_date_time(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(&squeeze(s), "%a %b %d %H:%M:%S %Y").ok()
+}
+
+/// Parses a time like "12:30:05" or "12:30"
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+        .ok()
+}
+
+/// Returns the network and buffer a log is of, guessed from its path the
+/// way the client names its logs by default
+pub fn guess_buffer(source: LogSource, fp: &Path) -> Option<(String, String)> {
+    let stem = fp.file_stem()?.to_str()?;
+    match source {
+        // irc.<network>.<buffer>.weechatlog
+        LogSource::Weechat => {
+            let name = stem.strip_prefix("irc.")?;
+            let (network, buffer) = name.split_once('.')?;
+            match (network, buffer) {
+                ("server", network) => Some((network.to_owned(), network.to_owned())),
+                _ => Some((network.to_owned(), buffer.to_owned())),
+            }
+        }
+        // <network>/<buffer>.log
+        LogSource::Irssi | LogSource::Hexchat => {
+            let network = fp.parent()?.file_name()?.to_str()?;
+            Some((network.to_owned(), stem.to_owned()))
+        }
+    }
+}
+
+/// Imports other clients' logs into the logs under logs_dir. Events that
+/// were imported before are skipped. Returns the number of events
+/// imported.
+pub fn import_logs(options: &ImportOptions, log: &LogConfig, logs_dir: &Path) -> Result<usize> {
+    let store = if log.sqlite {
+        Some(Store::open(&logs_dir.join(STORE_FILE))?)
+    } else {
+        None
+    };
+    let mut imported = 0;
+
+    for fp in &options.files {
+        let (network, buffer) = match (guess_buffer(options.source, fp), &options.network) {
+            (Some((_, buffer)), Some(network)) => (network.clone(), buffer),
+            (Some(guess), None) => guess,
+            (None, _) => {
+                let msg = format!("Can't tell which buffer {} is of", fp.display());
+                return Err(Error::new(ErrorKind::InvalidInput, msg));
+            }
+        };
+
+        let text = fs::read(fp)?;
+        let text = String::from_utf8_lossy(&text);
+        let entries = match options.source {
+            LogSource::Irssi => parse_irssi(&text),
+            LogSource::Weechat => parse_weechat(&text),
+            LogSource::Hexchat => parse_hexchat(&text),
+        };
+
+        // Group the events by the log they belong in
+        let key = CaseMapping::default().fold(&buffer);
+        let mut logs: BTreeMap<PathBuf, Vec<JsonEvent>> = BTreeMap::new();
+        for entry in entries {
+            let time = match Local.from_local_datetime(&entry.time).earliest() {
+                Some(time) => time,
+                None => continue, // Skipped by a DST change
+            };
+            let time = time.with_timezone(&Utc);
+            let date = log.timezone.date(time);
+            let path = log.render(&network_name(&network), &file_name(&key), date);
+            logs.entry(logs_dir.join(path))
+                .or_default()
+                .push(JsonEvent {
+                    timestamp: time.format(LOG_TIME_FORMAT).to_string(),
+                    network: network.clone(),
+                    buffer: buffer.clone(),
+                    kind: entry.kind.to_owned(),
+                    nick: entry.nick,
+                    hostmask: entry.hostmask,
+                    text: entry.text,
+                    tags: BTreeMap::new(),
+                    msgid: None,
+                });
+        }
+
+        for (fp, events) in logs {
+            let text_lines = events.iter().map(JsonEvent::to_log_line).collect();
+            let json_lines = events
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<serde_json::Result<_>>()?;
+            let json_fp = fp.with_extension("jsonl");
+            let added = if log.format.has_text() {
+                let added = merge_log(&fp, text_lines)?;
+                if log.format.has_json() {
+                    merge_log(&json_fp, json_lines)?;
+                }
+                added
+            } else {
+                merge_log(&json_fp, json_lines)?
+            };
+
+            if let Some(ref store) = store {
+                for i in &added {
+                    store.insert(&events[*i], &key)?;
+                }
+            }
+            imported += added.len();
+        }
+    }
+    Ok(imported)
+}
@@ -1,32 +1,76 @@
-use crate::channel::Channel;
-use crate::connection::Connection;
-use std::io::Result;
+/// Name of the buffer messages highlighting us are copied to
+pub const MENTIONS_BUFFER: &str = "*mentions*";
+
+use crate::channel::{Activity, Channel, Event, STORE_FILE};
+use crate::command::{join_lines, split_key, Command};
+use crate::config::{config_file, save_autojoin, Config, ConfigDiff};
+use crate::config_dir;
+use crate::connection::{CaseMapping, Connection};
+use crate::export::{export, ExportFormat, ExportOptions};
+use crate::ignore::Ignore;
+use crate::logfile::{file_name, network_name};
+use crate::netsplit::Netsplit;
+use crate::notify::Notification;
+use crate::scrollback::{Line, Scroll, Style, View};
+use crate::search::Hit;
+use crate::settings::Settings;
+use crate::store::Store;
+use chrono::{Local, NaiveDate, Utc};
+use regex::Regex;
+use std::fs::{create_dir_all, write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub struct Interface {
-    channels: Mutex<Vec<Channel>>,
-    conn: Mutex<Connection>,
+    channels: Mutex<Vec<Channel>>, // Grouped by network, its own buffer first
+    conns: Mutex<Vec<Connection>>,
+    settings: Mutex<Settings>,
+    search_hits: Mutex<Vec<Hit>>, // Results of the last search of the logs
+    config: Mutex<Config>,        // As last loaded, to tell what a reload changes
+    outbox: Mutex<Vec<(Instant, String, String)>>, // When to send lines to which network
+    netsplits: Mutex<Vec<Netsplit>>,
+    store: Mutex<Option<Arc<Store>>>, // Shared by all logged channels once opened
     active_channel: AtomicUsize,
+    sessions: AtomicUsize, // Number of connections made
     shutdown_flag: AtomicBool,
     refresh_buffers_flag: AtomicBool,
+    refresh_output_flag: AtomicBool,
+    search_nav_flag: AtomicBool,
+    focused_flag: AtomicBool, // Whether the terminal has focus, as far as we know
 }
 
 impl Interface {
-    pub fn new(conn: Connection) -> Self {
-        let root = vec![Channel::new(&conn.server, &conn.server)];
-        let conn = Mutex::new(conn);
-        let channels = Mutex::new(root);
+    /// Creates an interface without networks, which must be added before
+    /// the UI is drawn
+    pub fn new(settings: Settings) -> Self {
+        let channels = Mutex::new(Vec::new());
         let active_channel = AtomicUsize::new(0);
         let shutdown_flag = AtomicBool::new(false);
         let refresh_buffers_flag = AtomicBool::new(false);
+        let refresh_output_flag = AtomicBool::new(true);
+        let config = config_file()
+            .and_then(|fp| Config::load(&fp).ok())
+            .unwrap_or_default();
 
         Self {
             channels,
-            conn,
+            conns: Mutex::new(Vec::new()),
+            settings: Mutex::new(settings),
+            search_hits: Mutex::new(Vec::new()),
+            config: Mutex::new(config),
+            outbox: Mutex::new(Vec::new()),
+            netsplits: Mutex::new(Vec::new()),
+            store: Mutex::new(None),
             active_channel,
+            sessions: AtomicUsize::new(0),
             shutdown_flag,
             refresh_buffers_flag,
+            refresh_output_flag,
+            search_nav_flag: AtomicBool::new(false),
+            focused_flag: AtomicBool::new(true),
         }
     }
 
@@ -36,29 +80,156 @@ impl Interface {
         channels.len()
     }
 
-    /// Returns the position channel chan in the vector
-    pub fn get_channel_pos(&self, chan: &str) -> Option<usize> {
-        let channels = self.channels.lock().unwrap();
-        let mut names = channels.iter().map(|c| c.get_id());
-        names.position(|c| c == chan)
+    /// Adds a network the client is connected to, its buffer showing the
+    /// end of its log as backlog. A network connected to before is
+    /// reconnected to, keeping its buffers. Returns the connection's
+    /// session.
+    pub fn add_network(&self, mut conn: Connection) -> usize {
+        let session = self.sessions.fetch_add(1, Ordering::Relaxed) + 1;
+        conn.session = session;
+        let network = conn.server.clone();
+        {
+            let mut conns = self.conns.lock().unwrap();
+            match conns.iter().position(|c| c.server == network) {
+                Some(pos) => conns[pos] = conn,
+                None => conns.push(conn),
+            }
+        }
+
+        if self.get_root_pos(&network).is_none() {
+            self.push_channel(self.open_channel(&network, &network));
+            self.set_refresh_buffers_flag();
+        }
+        session
     }
 
-    /// Returns whether channel chan is the currently active channel
-    pub fn is_active(&self, chan: &str) -> bool {
-        if let Some(pos) = self.get_channel_pos(chan) {
-            pos == self.get_active_channel_pos()
+    /// Marks the connection to network as closed if it is still the one of
+    /// session, keeping its buffers to reconnect to
+    pub fn close_network(&self, network: &str, session: usize) -> bool {
+        let mut conns = self.conns.lock().unwrap();
+        match conns.iter_mut().find(|c| c.server == network) {
+            Some(conn) if conn.session == session => {
+                conn.disconnect();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Disconnects from network, removing its buffers
+    pub fn remove_network(&self, network: &str) {
+        {
+            let mut conns = self.conns.lock().unwrap();
+            for conn in conns.iter_mut().filter(|c| c.server == network) {
+                conn.disconnect();
+            }
+            conns.retain(|c| c.server != network);
+        }
+
+        let mut channels = self.channels.lock().unwrap();
+        let active = self.get_active_channel_pos();
+        let removed_before = channels
+            .iter()
+            .take(active)
+            .filter(|c| c.get_server() == network)
+            .count();
+        let active_removed = channels
+            .get(active)
+            .is_some_and(|c| c.get_server() == network);
+        channels.retain(|c| c.get_server() != network);
+        let active = if active_removed {
+            0
         } else {
-            false
+            active - removed_before
+        };
+        let last = channels.len().saturating_sub(1);
+        drop(channels);
+        self.store_active_channel(active.min(last));
+        self.set_refresh_buffers_flag();
+    }
+
+    /// Closes the connections to all networks
+    pub fn disconnect_all(&self) {
+        let mut conns = self.conns.lock().unwrap();
+        for conn in conns.iter_mut() {
+            conn.disconnect();
+        }
+    }
+
+    /// Returns the names of the networks added, connected to or not
+    pub fn get_networks(&self) -> Vec<String> {
+        let conns = self.conns.lock().unwrap();
+        conns.iter().map(|c| c.server.clone()).collect()
+    }
+
+    /// Returns whether the client is connected to network
+    pub fn is_connected(&self, network: &str) -> bool {
+        let conns = self.conns.lock().unwrap();
+        conns
+            .iter()
+            .any(|c| c.server == network && c.stream.is_some())
+    }
+
+    /// Returns the connection to target: a network of the configuration,
+    /// else the server at host[:port], with the active network's nick
+    pub fn new_connection(&self, target: &str) -> std::result::Result<Connection, String> {
+        let nick = self.get_nick(&self.get_active_network());
+        let conn = self.config.lock().unwrap().connection_to(target, &nick)?;
+        if self.is_connected(&conn.server) {
+            return Err(format!("Already connected to {}", conn.server));
         }
+        Ok(conn)
+    }
+
+    /// Returns the network of the currently active channel
+    pub fn get_active_network(&self) -> String {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(self.get_active_channel_pos())
+            .map(|chan| chan.get_server().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the position of network's own buffer in the vector
+    pub fn get_root_pos(&self, network: &str) -> Option<usize> {
+        let channels = self.channels.lock().unwrap();
+        channels.iter().position(|c| is_root(c, network))
+    }
+
+    /// Returns whether the channel at position pos is a network's own buffer
+    pub fn is_root(&self, pos: usize) -> bool {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(pos)
+            .is_some_and(|c| c.get_id() == c.get_server())
+    }
+
+    /// Returns whether the active buffer is a channel or query, the only ones
+    /// plain text can be sent to
+    pub fn is_active_chat(&self) -> bool {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(self.get_active_channel_pos())
+            .is_some_and(|c| !c.is_scratch() && c.get_id() != c.get_server())
+    }
+
+    /// Returns the position of channel chan of network in the vector
+    pub fn get_channel_pos(&self, network: &str, chan: &str) -> Option<usize> {
+        let channels = self.channels.lock().unwrap();
+        position(&channels, network, chan)
+    }
+
+    /// Returns whether channel chan of network is the currently active
+    /// channel
+    pub fn is_active(&self, network: &str, chan: &str) -> bool {
+        let channels = self.channels.lock().unwrap();
+        position(&channels, network, chan) == Some(self.get_active_channel_pos())
     }
 
     /// Returns the name of the channel at position pos
     pub fn get_channel(&self, pos: usize) -> Option<String> {
         let channels = self.channels.lock().unwrap();
-        match channels.get(pos) {
-            Some(chan) => Some(chan.get_id().to_owned()),
-            None => None,
-        }
+        channels.get(pos).map(|chan| chan.get_id().to_owned())
     }
 
     /// Returns the name of the currently active channel
@@ -71,25 +242,690 @@ impl Interface {
             .to_owned()
     }
 
-    /// Pushes a channel to the channel vector
-    pub fn push_channel(&self, chan: Channel) {
+    /// Returns the names of all channels in the vector
+    pub fn get_channels(&self) -> Vec<String> {
+        let channels = self.channels.lock().unwrap();
+        channels.iter().map(|c| c.get_id().to_owned()).collect()
+    }
+
+    /// Returns the members of channel chan of network, most recently
+    /// active first
+    pub fn get_nicks(&self, network: &str, chan: &str) -> Vec<String> {
+        let channels = self.channels.lock().unwrap();
+        match channels
+            .iter()
+            .find(|c| c.get_server() == network && c.is(chan))
+        {
+            Some(chan) => chan.get_nicks().to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Applies f to channel chan of network, if it is open. The channel is
+    /// looked up and used under one lock, so f can't get another one.
+    fn with_channel<T>(
+        &self,
+        network: &str,
+        chan: &str,
+        f: impl FnOnce(&mut Channel) -> T,
+    ) -> Option<T> {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .iter_mut()
+            .find(|c| c.get_server() == network && c.is(chan))
+            .map(f)
+    }
+
+    /// Adds nick to the members of channel chan of network
+    pub fn add_nick(&self, network: &str, chan: &str, nick: &str) {
+        self.with_channel(network, chan, |chan| chan.add_nick(nick));
+    }
+
+    /// Removes nick from the members of channel chan of network
+    pub fn remove_nick(&self, network: &str, chan: &str, nick: &str) {
+        self.with_channel(network, chan, |chan| chan.remove_nick(nick));
+    }
+
+    /// Marks nick as the most recently active member of channel chan of
+    /// network
+    pub fn touch_nick(&self, network: &str, chan: &str, nick: &str) {
+        self.with_channel(network, chan, |chan| chan.touch_nick(nick));
+    }
+
+    /// Removes nick from the members of all channels of network, e.g.
+    /// after it quit
+    pub fn quit_nick(&self, network: &str, nick: &str) {
+        let mut channels = self.channels.lock().unwrap();
+        for chan in channels.iter_mut().filter(|c| c.get_server() == network) {
+            chan.remove_nick(nick);
+        }
+    }
+
+    /// Returns the channels of network nick is a member of
+    pub fn get_nick_channels(&self, network: &str, nick: &str) -> Vec<String> {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .iter()
+            .filter(|c| c.get_server() == network && c.has_nick(nick))
+            .map(|c| c.get_id().to_owned())
+            .collect()
+    }
+
+    /// Returns whether a join or the like of nick in channel chan of
+    /// network is noise to hide, as nick didn't speak there lately
+    pub fn is_noise(&self, network: &str, chan: &str, nick: &str) -> bool {
+        let window = match self.settings.lock().unwrap().smart_filter {
+            Some(window) => window,
+            None => return false,
+        };
+        let since = Utc::now() - chrono::Duration::from_std(window).unwrap_or_default();
+        self.with_channel(network, chan, |c| !c.has_spoken_since(nick, since))
+            .unwrap_or(false)
+    }
+
+    /// Notes that nick quit from the channels of network because servers
+    /// split
+    pub fn add_netsplit_quit(&self, network: &str, servers: (&str, &str), nick: &str) {
+        let channels = self.get_nick_channels(network, nick);
+        let mut netsplits = self.netsplits.lock().unwrap();
+        let is_same = |s: &&mut Netsplit| {
+            s.network == network && s.servers.0 == servers.0 && s.servers.1 == servers.1
+        };
+        match netsplits.iter_mut().find(is_same) {
+            Some(netsplit) => netsplit.add_quit(nick, &channels),
+            None => {
+                let mut netsplit = Netsplit::new(network, servers);
+                netsplit.add_quit(nick, &channels);
+                netsplits.push(netsplit);
+            }
+        }
+    }
+
+    /// Notes that nick joined channel of network, returning whether it is
+    /// back from a netsplit
+    pub fn add_netsplit_join(&self, network: &str, channel: &str, nick: &str) -> bool {
+        let casemapping = self
+            .with_conn(network, |conn| conn.casemapping)
+            .unwrap_or_default();
+        let mut netsplits = self.netsplits.lock().unwrap();
+        netsplits
+            .iter_mut()
+            .filter(|s| s.network == network)
+            .any(|s| s.add_join(nick, channel, casemapping))
+    }
+
+    /// Prints what netsplits that calmed down caused in each channel, and
+    /// forgets those long over
+    pub fn flush_netsplits(&self) {
+        let now = Instant::now();
+        let summaries: Vec<_> = {
+            let mut netsplits = self.netsplits.lock().unwrap();
+            let summaries = netsplits
+                .iter_mut()
+                .flat_map(|s| {
+                    let network = s.network.clone();
+                    s.take_summaries(now)
+                        .into_iter()
+                        .map(move |(channel, line)| (network.clone(), channel, line))
+                })
+                .collect();
+            netsplits.retain(|s| !s.is_over(now));
+            summaries
+        };
+        for (network, channel, line) in summaries {
+            self.print_to_chan(&network, &channel, &line);
+        }
+    }
+
+    /// Renames nick in the members of all channels of network
+    pub fn rename_nick(&self, network: &str, old: &str, new: &str) {
+        let mut channels = self.channels.lock().unwrap();
+        for chan in channels.iter_mut().filter(|c| c.get_server() == network) {
+            chan.rename_nick(old, new);
+        }
+    }
+
+    /// Creates a channel for id on network, showing the end of its log as
+    /// backlog
+    pub fn open_channel(&self, network: &str, id: &str) -> Channel {
+        let casemapping = self.with_conn(network, |conn| conn.casemapping);
+        let settings = self.settings.lock().unwrap();
+        let mut chan = Channel::new(id, network, &settings.log, casemapping.unwrap_or_default());
+        if settings.log.sqlite {
+            self.share_store(&mut chan);
+        }
+        // A backlog that can't be read isn't worth failing to open over
+        let _ = chan.load_backlog(settings.backlog_lines);
+        chan
+    }
+
+    /// Shares the SQLite store with chan, opening it the first time. Says
+    /// so in chan if it can't.
+    fn share_store(&self, chan: &mut Channel) {
+        let fp = match chan.get_logs_dir() {
+            Some(dir) => dir.join(STORE_FILE),
+            None => return,
+        };
+        let mut store = self.store.lock().unwrap();
+        if store.is_none() {
+            match Store::open(&fp) {
+                Ok(opened) => *store = Some(Arc::new(opened)),
+                Err(e) => {
+                    let msg = format!("SQLite store disabled: {}", e);
+                    chan.print(Line::new(&msg, Style::Info));
+                    return;
+                }
+            }
+        }
+        chan.set_store(store.clone().unwrap());
+    }
+
+    /// Returns the SQLite store, if events are recorded there
+    pub fn get_store(&self) -> Option<Arc<Store>> {
+        self.store.lock().unwrap().clone()
+    }
+
+    /// Adds a channel after the other channels of its network
+    pub fn push_channel(&self, mut chan: Channel) {
+        let network = chan.get_server().to_owned();
+        let log_errors = chan.take_log_errors();
         let mut channels = self.channels.lock().unwrap();
-        channels.push(chan);
+        self.insert_channel(&mut channels, chan);
+        drop(channels);
+        self.report_log_errors(&network, log_errors);
+    }
+
+    /// Inserts a channel into channels after the other channels of its
+    /// network, keeping the same channel active. Returns its position.
+    fn insert_channel(&self, channels: &mut Vec<Channel>, chan: Channel) -> usize {
+        let pos = channels
+            .iter()
+            .rposition(|c| c.get_server() == chan.get_server())
+            .map_or(channels.len(), |last| last + 1);
+        channels.insert(pos, chan);
+        let active = self.get_active_channel_pos();
+        if pos <= active && channels.len() > 1 {
+            self.active_channel.store(active + 1, Ordering::Relaxed);
+        }
+        pos
     }
 
-    /// Removes the channel at position pos from the vector
-    pub fn remove_channel(&self, pos: usize) {
+    /// Removes channel chan of network, switching to the channel before it
+    /// if it was the active one
+    pub fn remove_channel(&self, network: &str, chan: &str) {
         let mut channels = self.channels.lock().unwrap();
+        let pos = match position(&channels, network, chan) {
+            Some(pos) => pos,
+            None => return,
+        };
         channels.remove(pos);
+        let active = self.get_active_channel_pos();
+        if pos == active {
+            self.activate(&mut channels, pos.saturating_sub(1));
+        } else if pos < active {
+            self.activate(&mut channels, active - 1);
+        }
+    }
+
+    /// Renames channel old of network to new, e.g. a query whose nick
+    /// changed, unless a channel named new is open already. Logs that
+    /// can't be moved are reported to the network. Returns whether the
+    /// channel was renamed.
+    pub fn rename_channel(&self, network: &str, old: &str, new: &str) -> bool {
+        let mut channels = self.channels.lock().unwrap();
+        let taken = channels
+            .iter()
+            .any(|c| c.get_server() == network && c.is(new) && !c.is(old));
+        let renamed = match channels
+            .iter_mut()
+            .find(|c| c.get_server() == network && c.is(old))
+        {
+            Some(chan) if !taken => chan.rename(new),
+            _ => return false,
+        };
+        drop(channels);
+        self.set_refresh_buffers_flag();
+        if let Err(e) = renamed {
+            let printable = format!("Could not move the logs to {}: {}", new, e);
+            self.print_to_network(network, &printable);
+        }
+        true
+    }
+
+    /// Logs an event to channel chan of network, noting its activity if
+    /// the channel isn't shown. Returns whether the channel is open.
+    pub fn write_to_chan(&self, network: &str, chan: &str, event: &Event<'_>) -> bool {
+        self.write_to(|c| c.get_server() == network && c.is(chan), event)
+    }
+
+    /// Logs an event to the first channel is_target holds for, noting its
+    /// activity if the channel isn't shown. Returns whether there is one.
+    fn write_to(&self, is_target: impl Fn(&Channel) -> bool, event: &Event<'_>) -> bool {
+        let mut channels = self.channels.lock().unwrap();
+        let pos = match channels.iter().position(is_target) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        let active = pos == self.get_active_channel_pos();
+        let chan = &mut channels[pos];
+        chan.write(event);
+        if !active && !event.hidden {
+            chan.add_activity(Activity::of(event));
+            self.set_refresh_buffers_flag();
+        }
+        let network = chan.get_server().to_owned();
+        let log_errors = chan.take_log_errors();
+        drop(channels);
+        if active {
+            self.set_refresh_output_flag();
+        }
+        self.report_log_errors(&network, log_errors);
+        true
+    }
+
+    /// Sends cmd to network and logs what was sent to channel chan of
+    /// network. It is logged first, so the channel can't be renamed or
+    /// closed in between, unless there is no connection to send it on.
+    pub fn send_to_chan(
+        &self,
+        network: &str,
+        chan: &str,
+        cmd: &Command<'_>,
+        event: &Event<'_>,
+    ) -> Result<()> {
+        if self.with_conn(network, |conn| conn.stream.is_some()) == Some(true) {
+            self.write_to_chan(network, chan, event);
+        }
+        self.send(network, cmd)
+    }
+
+    /// Prints the failures to log in network to its own buffer
+    fn report_log_errors(&self, network: &str, log_errors: Vec<String>) {
+        for msg in log_errors {
+            self.print_to_network(network, &msg);
+        }
+    }
+
+    /// Copies an event of buffer that highlights us into network's
+    /// mentions buffer, opening it if needed
+    pub fn add_mention(&self, network: &str, buffer: &str, event: &Event<'_>) {
+        let text = format!("{} {}", buffer, event.printable);
+        let line = Line::with_time(Some(event.time), &text, Style::Highlight);
+        let mut channels = self.channels.lock().unwrap();
+        let pos = match position(&channels, network, MENTIONS_BUFFER) {
+            Some(pos) => pos,
+            None => self.insert_channel(&mut channels, Channel::scratch(MENTIONS_BUFFER, network)),
+        };
+        channels[pos].print(line);
+        if pos == self.get_active_channel_pos() {
+            self.set_refresh_output_flag();
+        } else {
+            channels[pos].add_activity(Activity::Highlight);
+        }
+        self.set_refresh_buffers_flag();
+    }
+
+    /// Returns what happened in the channel at position pos since it was
+    /// last shown, and the number of messages
+    pub fn get_activity(&self, pos: usize) -> (Activity, usize) {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(pos)
+            .map_or((Activity::None, 0), Channel::get_activity)
+    }
+
+    /// Returns the position of the next channel after the active one with
+    /// the most important activity, if any has some
+    pub fn next_active_channel(&self) -> Option<usize> {
+        let channels = self.channels.lock().unwrap();
+        let active = self.get_active_channel_pos();
+        let len = channels.len();
+        (1..len)
+            .map(|i| (active + i) % len)
+            .map(|pos| (pos, channels[pos].get_activity().0))
+            .filter(|(_, activity)| *activity > Activity::None)
+            // The first of the most important wins
+            .rev()
+            .max_by_key(|(_, activity)| *activity)
+            .map(|(pos, _)| pos)
     }
 
-    /// Logs a message s to the channel as position pos
-    pub fn write_to_chan(&self, pos: usize, s: &str) -> Result<()> {
+    /// Prints a message from the client to channel chan of network
+    /// without logging it
+    pub fn print_to_chan(&self, network: &str, chan: &str, s: &str) {
+        self.print_to(|c| c.get_server() == network && c.is(chan), s);
+    }
+
+    /// Prints a message from the client to the currently active channel
+    /// without logging it
+    pub fn print_to_active(&self, s: &str) {
+        let active = self.get_active_channel_pos();
         let mut channels = self.channels.lock().unwrap();
-        if let Some(ref mut chan) = channels.get_mut(pos) {
-            chan.write(s)?;
+        if let Some(chan) = channels.get_mut(active) {
+            chan.print(Line::new(s, Style::Info));
+        }
+        self.set_refresh_output_flag();
+    }
+
+    /// Prints a message from the client to the first channel is_target
+    /// holds for without logging it
+    fn print_to(&self, is_target: impl Fn(&Channel) -> bool, s: &str) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(pos) = channels.iter().position(is_target) {
+            channels[pos].print(Line::new(s, Style::Info));
+            if pos == self.get_active_channel_pos() {
+                self.set_refresh_output_flag();
+            }
+        }
+    }
+
+    /// Logs an event to network's own buffer
+    pub fn write_to_network(&self, network: &str, event: &Event<'_>) {
+        self.write_to(|c| is_root(c, network), event);
+    }
+
+    /// Prints a message from the client to network's own buffer without
+    /// logging it
+    pub fn print_to_network(&self, network: &str, s: &str) {
+        self.print_to(|c| is_root(c, network), s);
+    }
+
+    /// Scrolls the output of the currently active channel
+    pub fn scroll_active_channel(&self, scroll: Scroll) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(chan) = channels.get_mut(self.get_active_channel_pos()) {
+            chan.scroll(scroll);
+        }
+        self.set_refresh_output_flag();
+    }
+
+    /// Returns whether the output of the currently active channel is
+    /// scrolled up from its end
+    pub fn is_active_scrolled(&self) -> bool {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(self.get_active_channel_pos())
+            .is_some_and(Channel::is_scrolled)
+    }
+
+    /// Returns the rows of the currently active channel's scrollback to
+    /// display in a window of the given size
+    pub fn get_active_view(&self, width: usize, height: usize) -> View {
+        let timestamp_format = self.settings.lock().unwrap().timestamp_format.clone();
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(self.get_active_channel_pos())
+            .unwrap()
+            .view(width, height, &timestamp_format)
+    }
+
+    /// Searches the scrollback of the currently active channel for lines
+    /// matching regex and jumps to the newest match. Returns the number of
+    /// matches.
+    pub fn search_active_channel(&self, regex: Regex) -> usize {
+        let mut channels = self.channels.lock().unwrap();
+        let count = match channels.get_mut(self.get_active_channel_pos()) {
+            Some(chan) => {
+                let count = chan.search(regex);
+                chan.next_match(true);
+                count
+            }
+            None => 0,
+        };
+        self.search_nav_flag.store(count > 0, Ordering::Relaxed);
+        self.set_refresh_output_flag();
+        count
+    }
+
+    /// Jumps to the next older or newer search match in the currently
+    /// active channel. Returns the match's number and the number of matches.
+    pub fn next_match(&self, older: bool) -> Option<(usize, usize)> {
+        let mut channels = self.channels.lock().unwrap();
+        let chan = channels.get_mut(self.get_active_channel_pos())?;
+        self.set_refresh_output_flag();
+        chan.next_match(older)
+    }
+
+    /// Ends the search of the currently active channel
+    pub fn clear_search(&self) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(chan) = channels.get_mut(self.get_active_channel_pos()) {
+            chan.clear_search();
+        }
+        self.end_search_nav();
+        self.set_refresh_output_flag();
+    }
+
+    /// Returns the regex the currently active channel is searched for
+    pub fn get_active_search(&self) -> Option<Regex> {
+        let channels = self.channels.lock().unwrap();
+        let chan = channels.get(self.get_active_channel_pos())?;
+        chan.search_regex().cloned()
+    }
+
+    /// Returns whether n/N currently jump between search matches
+    pub fn is_search_nav(&self) -> bool {
+        self.search_nav_flag.load(Ordering::Relaxed)
+    }
+
+    /// Makes n/N keys be typed again, keeping matches highlighted
+    pub fn end_search_nav(&self) {
+        self.search_nav_flag.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns the directory holding the logs of the active network, if
+    /// there is one
+    pub fn get_logs_dir(&self) -> Option<PathBuf> {
+        let network = network_name(&self.get_active_network());
+        let log = &self.settings.lock().unwrap().log;
+        log.dir
+            .as_ref()
+            .map(|logs| logs.join(log.network_dir(&network)))
+    }
+
+    /// Renders the active channel's log from one day to another into the
+    /// exports directory, today's only if no days are given. Returns the
+    /// path of the export.
+    pub fn export_active_channel(
+        &self,
+        format: ExportFormat,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<PathBuf> {
+        let config =
+            config_dir().ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))?;
+        let buffer = self.get_active_channel();
+        let network = self.get_active_network();
+        let log = self.settings.lock().unwrap().log.clone();
+        let from = from.unwrap_or_else(|| log.timezone.today());
+        let options = ExportOptions {
+            format,
+            network,
+            buffer,
+            from: Some(from),
+            to: Some(to.unwrap_or(from)),
+            output: None,
+        };
+        let casemapping = self
+            .with_conn(&options.network, |conn| conn.casemapping)
+            .unwrap_or_default();
+        let logs = log
+            .dir
+            .as_ref()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))?;
+        let rendered = export(&options, &log, logs, casemapping)?;
+
+        let mut name = format!("{}-{}", file_name(&options.buffer), from);
+        if let Some(to) = to.filter(|to| *to != from) {
+            name.push_str(&format!("_{}", to));
+        }
+        let dir = config.join("exports");
+        create_dir_all(&dir)?;
+        let fp = dir.join(format!("{}.{}", name, format.extension()));
+        write(&fp, rendered)?;
+        Ok(fp)
+    }
+
+    /// Re-reads the configuration file, applying what can be changed in
+    /// place: settings, and channels added to or removed from autojoin,
+    /// which are joined or left. Returns what changed in the settings, and
+    /// for each network.
+    pub fn reload_config(
+        &self,
+    ) -> std::result::Result<(ConfigDiff, Vec<(String, ConfigDiff)>), String> {
+        let fp = config_file().ok_or("HOME is not set")?;
+        let new = Config::load(&fp).map_err(|e| e.to_string())?;
+        // Settings are checked before anything is changed
+        let mut loaded = Settings::default();
+        new.apply(&mut loaded)?;
+
+        let (mut diff, diffs) = {
+            let old = self.config.lock().unwrap();
+            let diffs: Vec<_> = self
+                .get_networks()
+                .into_iter()
+                .map(|network| {
+                    let diff = new.diff_network(&old, &network);
+                    (network, diff)
+                })
+                .collect();
+            (new.diff(&old), diffs)
+        };
+        let mut settings = self.settings.lock().unwrap();
+        diff.keep(&settings.overrides);
+        for key in &diff.applied {
+            match key.as_str() {
+                "timestamp" => settings.timestamp_format = loaded.timestamp_format.clone(),
+                "backlog" => settings.backlog_lines = loaded.backlog_lines,
+                "smart_filter" => settings.smart_filter = loaded.smart_filter,
+                "highlight" => settings.highlights = loaded.highlights.clone(),
+                "notify" => settings.notifier = loaded.notifier.clone(),
+                "ignore" => {
+                    // Ignores added with :ignore are kept
+                    settings.ignores.retain(|ignore| !ignore.configured);
+                    settings.ignores.extend(loaded.ignores.iter().cloned());
+                }
+                _ => (),
+            }
+        }
+        drop(settings);
+
+        for (network, diff) in &diffs {
+            // Buffers are opened and closed as the server confirms
+            let targets = self.with_conn(network, |conn| conn.join_targets).flatten();
+            for line in join_lines(&diff.join, targets) {
+                self.queue_line(network, line);
+            }
+            if !diff.part.is_empty() {
+                let channels: Vec<_> = diff.part.iter().map(|e| split_key(e).0).collect();
+                self.queue_line(network, Command::Part(&channels).to_unwrapped_string());
+            }
+            if let Some(config) = new.networks.get(network) {
+                self.with_conn(network, |conn| {
+                    conn.autojoin = config.autojoin.clone();
+                    conn.save_autojoin = config.save_autojoin;
+                });
+            }
+        }
+
+        *self.config.lock().unwrap() = new;
+        self.set_refresh_buffers_flag();
+        self.set_refresh_output_flag();
+        Ok((diff, diffs))
+    }
+
+    /// Sends a command to network
+    pub fn send(&self, network: &str, cmd: &Command<'_>) -> Result<()> {
+        match cmd.to_string() {
+            Some(line) => self.send_line(network, &line),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends a raw line to network
+    pub fn send_line(&self, network: &str, line: &str) -> Result<()> {
+        self.with_conn(network, |conn| conn.send(line))
+            .unwrap_or_else(|| {
+                let msg = format!("Not connected to {}", network);
+                Err(Error::new(ErrorKind::NotConnected, msg))
+            })
+    }
+
+    /// Queues a raw line to be sent to network by the write thread
+    pub fn queue_line(&self, network: &str, line: String) {
+        self.queue_line_after(network, line, Duration::default());
+    }
+
+    /// Queues a raw line to be sent to network once delay has passed
+    pub fn queue_line_after(&self, network: &str, line: String, delay: Duration) {
+        let at = Instant::now() + delay;
+        self.outbox
+            .lock()
+            .unwrap()
+            .push((at, network.to_owned(), line));
+    }
+
+    /// Returns the queued lines that are due with their networks, removing
+    /// them from the queue
+    pub fn take_queued_lines(&self) -> Vec<(String, String)> {
+        let now = Instant::now();
+        let mut outbox = self.outbox.lock().unwrap();
+        let (due, later): (Vec<_>, Vec<_>) = std::mem::take(&mut *outbox)
+            .into_iter()
+            .partition(|(at, ..)| *at <= now);
+        *outbox = later;
+        due.into_iter()
+            .map(|(_, network, line)| (network, line))
+            .collect()
+    }
+
+    /// Returns whether logs are only written as JSON
+    pub fn logs_json_only(&self) -> bool {
+        !self.settings.lock().unwrap().log.format.has_text()
+    }
+
+    /// Stores the results of a search of the logs
+    pub fn store_search_hits(&self, hits: Vec<Hit>) {
+        *self.search_hits.lock().unwrap() = hits;
+    }
+
+    /// Returns result n of the last search of the logs
+    pub fn get_search_hit(&self, n: usize) -> Option<Hit> {
+        self.search_hits.lock().unwrap().get(n).cloned()
+    }
+
+    /// Shows lines in the unlogged buffer id, replacing what it showed
+    /// before, and switches to it
+    pub fn show_scratch(&self, id: &str, lines: Vec<Line>) {
+        let network = self.get_active_network();
+        let mut chan = Channel::scratch(id, &network);
+        for line in lines {
+            chan.print(line);
+        }
+
+        let mut channels = self.channels.lock().unwrap();
+        let pos = match position(&channels, &network, id) {
+            Some(pos) => {
+                channels[pos] = chan;
+                pos
+            }
+            None => self.insert_channel(&mut channels, chan),
+        };
+        self.activate(&mut channels, pos);
+        self.set_refresh_buffers_flag();
+    }
+
+    /// Switches to channel chan of network, returning whether it is open
+    pub fn show_channel(&self, network: &str, chan: &str) -> bool {
+        let mut channels = self.channels.lock().unwrap();
+        match position(&channels, network, chan) {
+            Some(pos) => {
+                self.activate(&mut channels, pos);
+                self.set_refresh_buffers_flag();
+                true
+            }
+            None => false,
         }
-        Ok(())
     }
 
     /// Gets the position of the currently active channel in the vector
@@ -97,9 +933,32 @@ impl Interface {
         self.active_channel.load(Ordering::Relaxed)
     }
 
-    /// Changes the currently active channel
+    /// Changes the currently active channel, redrawing the output window
+    /// from its scrollback
     pub fn store_active_channel(&self, n: usize) {
+        let mut channels = self.channels.lock().unwrap();
+        self.activate(&mut channels, n);
+    }
+
+    /// Makes the channel at position n of channels the active one
+    fn activate(&self, channels: &mut [Channel], n: usize) {
         self.active_channel.store(n, Ordering::Relaxed);
+        if let Some(chan) = channels.get_mut(n) {
+            chan.clear_activity();
+        }
+        self.end_search_nav();
+        self.set_refresh_output_flag();
+    }
+
+    /// Stores whether the terminal has focus, as it reports
+    pub fn set_focused(&self, focused: bool) {
+        self.focused_flag.store(focused, Ordering::Relaxed);
+    }
+
+    /// Returns whether the terminal has focus. Terminals that don't report
+    /// it are taken to always have it.
+    pub fn is_focused(&self) -> bool {
+        self.focused_flag.load(Ordering::Relaxed)
     }
 
     /// Sets the shutdown flag, causing all threads to terminate in their
@@ -113,26 +972,290 @@ impl Interface {
         self.shutdown_flag.load(Ordering::Relaxed)
     }
 
-    /// Toggles the refresh buffers flag
-    pub fn toggle_refresh_buffers_flag(&self) {
-        let arg = self.should_refresh_buffers();
-        self.refresh_buffers_flag.store(!arg, Ordering::Relaxed);
+    /// Sets the refresh buffers flag, causing the buffers bar to be redrawn
+    pub fn set_refresh_buffers_flag(&self) {
+        self.refresh_buffers_flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the refresh buffers flag was set, clearing it
+    pub fn take_refresh_buffers_flag(&self) -> bool {
+        self.refresh_buffers_flag.swap(false, Ordering::Relaxed)
+    }
+
+    /// Sets the refresh output flag, causing the output window to be
+    /// redrawn from the active channel's scrollback
+    pub fn set_refresh_output_flag(&self) {
+        self.refresh_output_flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the refresh output flag was set, clearing it
+    pub fn take_refresh_output_flag(&self) -> bool {
+        self.refresh_output_flag.swap(false, Ordering::Relaxed)
+    }
+
+    /// Applies f to the connection to network, if it was added
+    fn with_conn<T>(&self, network: &str, f: impl FnOnce(&mut Connection) -> T) -> Option<T> {
+        let mut conns = self.conns.lock().unwrap();
+        conns.iter_mut().find(|c| c.server == network).map(f)
+    }
+
+    /// Returns our nick on network
+    pub fn get_nick(&self, network: &str) -> String {
+        self.with_conn(network, |conn| conn.nick.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether name is a channel rather than a nick on network
+    pub fn is_channel(&self, network: &str, name: &str) -> bool {
+        self.with_conn(network, |conn| {
+            name.starts_with(|c| conn.chantypes.contains(c))
+        })
+        .unwrap_or_default()
     }
 
-    /// Returns whether the refresh buffers flag is set
-    pub fn should_refresh_buffers(&self) -> bool {
-        self.refresh_buffers_flag.load(Ordering::Relaxed)
+    /// Stores the characters channel names start with on network, as
+    /// announced by CHANTYPES
+    pub fn set_chantypes(&self, network: &str, chantypes: &str) {
+        self.with_conn(network, |conn| conn.chantypes = chantypes.to_owned());
     }
 
-    /// Returns the stored username
-    pub fn get_username(&self) -> String {
-        let conn = self.conn.lock().unwrap();
-        conn.username.clone()
+    /// Returns whether text sent to buffer on network highlights us
+    pub fn is_highlight(&self, network: &str, buffer: &str, text: &str) -> bool {
+        let (nick, casemapping) = match self.with_conn(network, |c| (c.nick.clone(), c.casemapping))
+        {
+            Some(conn) => conn,
+            None => return false,
+        };
+        let settings = self.settings.lock().unwrap();
+        settings
+            .highlights
+            .is_match(text, &nick, buffer, casemapping)
     }
 
-    /// Returns the stored server name
-    pub fn get_server(&self) -> String {
-        let conn = self.conn.lock().unwrap();
-        conn.server.clone()
+    /// Notifies of a message nick sent to buffer on network, unless the
+    /// buffer is shown in a focused terminal. Reports if that fails.
+    pub fn notify(&self, network: &str, buffer: &str, nick: &str, text: &str) {
+        if self.is_active(network, buffer) && self.is_focused() {
+            return;
+        }
+        let notification = Notification {
+            network,
+            buffer,
+            nick,
+            text,
+        };
+        let time = Local::now().time();
+        let notified = self
+            .settings
+            .lock()
+            .unwrap()
+            .notifier
+            .notify(&notification, time);
+        if let Err(e) = notified {
+            self.print_to_network(network, &format!("Could not notify: {}", e));
+        }
+    }
+
+    /// Returns the escape sequences of notifications to write to the
+    /// terminal, which only the UI thread does
+    pub fn take_notifications(&self) -> Vec<String> {
+        self.settings.lock().unwrap().notifier.take_escapes()
+    }
+
+    /// Returns whether cmd, sent from hostmask on network, is ignored.
+    /// What we do never is.
+    pub fn is_ignored(&self, network: &str, cmd: &Command<'_>, hostmask: &str) -> bool {
+        let own = cmd
+            .sender()
+            .is_some_and(|nick| self.is_own_nick(network, nick));
+        let casemapping = self.with_conn(network, |conn| conn.casemapping);
+        let mut settings = self.settings.lock().unwrap();
+        settings
+            .ignores
+            .retain(|ignore| !ignore.is_expired(Utc::now()));
+        !own && settings
+            .ignores
+            .iter()
+            .any(|ignore| ignore.is_match(cmd, hostmask, casemapping.unwrap_or_default()))
+    }
+
+    /// Adds an ignore, replacing any of the same mask
+    pub fn add_ignore(&self, ignore: Ignore) {
+        let mut settings = self.settings.lock().unwrap();
+        settings.ignores.retain(|i| i.mask != ignore.mask);
+        settings.ignores.push(ignore);
+    }
+
+    /// Removes the ignore of mask, or the one numbered so when listed,
+    /// returning it
+    pub fn remove_ignore(&self, mask: &str) -> Option<Ignore> {
+        let mut settings = self.settings.lock().unwrap();
+        let pos = match mask.parse::<usize>() {
+            Ok(n) if n < settings.ignores.len() => n,
+            _ => settings.ignores.iter().position(|i| i.mask == mask)?,
+        };
+        Some(settings.ignores.remove(pos))
+    }
+
+    /// Returns the ignores that haven't expired, as listed
+    pub fn get_ignores(&self) -> Vec<String> {
+        let mut settings = self.settings.lock().unwrap();
+        settings
+            .ignores
+            .retain(|ignore| !ignore.is_expired(Utc::now()));
+        settings.ignores.iter().map(Ignore::describe).collect()
+    }
+
+    /// Returns whether nick is ours on network
+    pub fn is_own_nick(&self, network: &str, nick: &str) -> bool {
+        self.with_conn(network, |conn| conn.casemapping.is_same(&conn.nick, nick))
+            .unwrap_or_default()
+    }
+
+    /// Remembers that we asked to join channel on network, to switch to it
+    /// once joined
+    pub fn add_pending_join(&self, network: &str, channel: &str) {
+        self.with_conn(network, |conn| conn.pending_joins.push(channel.to_owned()));
+    }
+
+    /// Returns whether we asked to join channel on network, forgetting it
+    pub fn take_pending_join(&self, network: &str, channel: &str) -> bool {
+        self.with_conn(network, |conn| {
+            let casemapping = conn.casemapping;
+            let pending = &mut conn.pending_joins;
+            match pending.iter().position(|c| casemapping.is_same(c, channel)) {
+                Some(pos) => {
+                    pending.remove(pos);
+                    true
+                }
+                None => false,
+            }
+        })
+        .unwrap_or_default()
+    }
+
+    /// Stores our nick on network, as the server confirmed it
+    pub fn set_nick(&self, network: &str, nick: &str) {
+        self.with_conn(network, |conn| conn.nick = nick.to_owned());
+    }
+
+    /// Picks the nick to try next on network after ours was taken,
+    /// returning it
+    pub fn next_nick(&self, network: &str) -> String {
+        self.with_conn(network, |conn| conn.next_nick().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the SASL username and password for network, if SASL is used
+    pub fn get_sasl(&self, network: &str) -> Option<(String, String)> {
+        self.with_conn(network, |conn| conn.sasl.clone()).flatten()
+    }
+
+    /// Returns the raw lines to send once registered on network
+    pub fn get_on_connect(&self, network: &str) -> Vec<String> {
+        self.with_conn(network, |conn| conn.on_connect.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the JOIN lines autojoining channels on network and how long
+    /// to wait before sending them, unless autojoin was done already
+    pub fn take_autojoin(&self, network: &str) -> Option<(Vec<String>, Duration)> {
+        self.with_conn(network, |conn| {
+            if conn.autojoined {
+                return None;
+            }
+            conn.autojoined = true;
+            let lines = join_lines(&conn.autojoin, conn.join_targets);
+            Some((lines, Duration::from_secs(conn.autojoin_delay)))
+        })
+        .flatten()
+    }
+
+    /// Adds channels joined to, or removes channels left from, network's
+    /// autojoin if it is kept up to date, saving it to the configuration
+    /// file
+    pub fn update_autojoin(&self, network: &str, channels: &[&str], joined: bool) -> Result<()> {
+        let casemapping = self.with_conn(network, |conn| {
+            if conn.save_autojoin {
+                update_entries(&mut conn.autojoin, channels, joined, conn.casemapping);
+            }
+            conn.save_autojoin.then_some(conn.casemapping)
+        });
+        let casemapping = match casemapping.flatten() {
+            Some(casemapping) => casemapping,
+            None => return Ok(()),
+        };
+
+        // Channels given with -c are left out of the file, as is what the
+        // file had changed to since it was loaded
+        let autojoin = match self.config.lock().unwrap().networks.get_mut(network) {
+            Some(config) => {
+                update_entries(&mut config.autojoin, channels, joined, casemapping);
+                config.autojoin.clone()
+            }
+            None => return Ok(()),
+        };
+        let fp = config_file().ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))?;
+        save_autojoin(&fp, network, &autojoin)
+    }
+
+    /// Stores the most channels network accepts in a JOIN, as announced
+    /// by TARGMAX
+    pub fn set_join_targets(&self, network: &str, targets: Option<usize>) {
+        self.with_conn(network, |conn| conn.join_targets = targets);
+    }
+
+    /// Changes how nicks and channel names of network are compared, after
+    /// the server announced its casemapping
+    pub fn set_casemapping(&self, network: &str, casemapping: CaseMapping) {
+        self.with_conn(network, |conn| conn.casemapping = casemapping);
+        let mut channels = self.channels.lock().unwrap();
+        let errors: Vec<_> = channels
+            .iter_mut()
+            .filter(|c| c.get_server() == network)
+            .filter_map(|chan| {
+                let e = chan.set_casemapping(casemapping).err()?;
+                Some(format!(
+                    "Could not move the logs of {}: {}",
+                    chan.get_id(),
+                    e
+                ))
+            })
+            .collect();
+        drop(channels);
+        self.report_log_errors(network, errors);
+    }
+}
+
+/// Returns the position of channel chan of network in channels
+fn position(channels: &[Channel], network: &str, chan: &str) -> Option<usize> {
+    channels
+        .iter()
+        .position(|c| c.get_server() == network && c.is(chan))
+}
+
+/// Returns whether chan is network's own buffer
+fn is_root(chan: &Channel, network: &str) -> bool {
+    chan.get_server() == network && chan.get_id() == network
+}
+
+/// Adds channels to autojoin entries, or removes them, keys included
+fn update_entries(
+    autojoin: &mut Vec<String>,
+    channels: &[&str],
+    joined: bool,
+    casemapping: CaseMapping,
+) {
+    for channel in channels {
+        let pos = autojoin
+            .iter()
+            .position(|e| casemapping.is_same(split_key(e).0, channel));
+        match (pos, joined) {
+            (None, true) => autojoin.push(channel.to_string()),
+            (Some(pos), false) => {
+                autojoin.remove(pos);
+            }
+            _ => (),
+        }
     }
 }
@@ -1,7 +1,38 @@
 pub mod argparse;
 pub mod channel;
 pub mod command;
+pub mod completion;
+pub mod config;
 pub mod connection;
+pub mod export;
+pub mod highlight;
+pub mod history;
+pub mod ignore;
+pub mod import;
 pub mod interface;
+pub mod logfile;
+pub mod netsplit;
+pub mod notify;
+pub mod scrollback;
+pub mod search;
+pub mod settings;
+pub mod stats;
+pub mod store;
 pub mod thread_tools;
 pub mod ui;
+
+const CONFIG_PATH: &str = ".config/minirc/";
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Returns the directory minirc keeps its configuration, logs and state in:
+/// minirc in XDG_CONFIG_HOME if it is set, else in ~/.config.
+pub fn config_dir() -> Option<PathBuf> {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(Path::new(&dir).join("minirc")),
+        _ => env::var("HOME")
+            .ok()
+            .map(|home| Path::new(&home).join(CONFIG_PATH)),
+    }
+}
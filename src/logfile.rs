@@ -0,0 +1,480 @@
+use crate::channel::{parse_log_line, JsonEvent, LogFormat, LOG_TIME_FORMAT};
+use crate::config_dir;
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader, Result};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogTimezone {
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl LogTimezone {
+    /// Returns the current date in the timezone
+    pub fn today(self) -> NaiveDate {
+        self.date(Utc::now())
+    }
+
+    /// Returns the date in the timezone at time
+    pub fn date(self, time: DateTime<Utc>) -> NaiveDate {
+        self.local_time(time).date()
+    }
+
+    /// Returns time as shown by clocks in the timezone
+    pub fn local_time(self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Self::Local => time.with_timezone(&Local).naive_local(),
+            Self::Fixed(offset) => time.with_timezone(&offset).naive_local(),
+        }
+    }
+}
+
+impl FromStr for LogTimezone {
+    type Err = String;
+
+    /// Parses "local", "UTC" or an offset like "+02:00"
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let err = || format!("Unknown timezone: {}", s);
+        match s {
+            "local" => Ok(Self::Local),
+            "UTC" | "utc" => Ok(Self::Fixed(FixedOffset::east_opt(0).unwrap())),
+            _ => {
+                let sign = match s.chars().next() {
+                    Some('+') => 1,
+                    Some('-') => -1,
+                    _ => return Err(err()),
+                };
+                let mut split = s[1..].splitn(2, ':');
+                let hours: i32 = split.next().and_then(|h| h.parse().ok()).ok_or_else(err)?;
+                let minutes: i32 = match split.next() {
+                    Some(m) => m.parse().map_err(|_| err())?,
+                    None => 0,
+                };
+                FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+                    .map(Self::Fixed)
+                    .ok_or_else(err)
+            }
+        }
+    }
+}
+
+/// Where and how channel logs are written
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    // Path of the text log relative to the logs directory. {network},
+    // {channel}, {yyyy}, {mm} and {dd} are replaced. JSON logs are written
+    // next to it with a .jsonl extension.
+    pub path: String,
+    pub timezone: LogTimezone, // Logs with dates in their path rotate at its midnight
+    pub compress: bool,        // Whether to gzip logs after rotating
+    pub retention_days: u32,   // Dated logs older than this get deleted, 0 keeps them
+    pub sqlite: bool,          // Whether to also record events in the SQLite store
+    pub dir: Option<PathBuf>,  // Directory the logs are kept in, none if HOME isn't set
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            path: String::from("{network}/{channel}.txt"),
+            timezone: LogTimezone::Local,
+            compress: false,
+            retention_days: 0,
+            sqlite: false,
+            dir: config_dir().map(|dir| dir.join("logs")),
+        }
+    }
+}
+
+impl LogConfig {
+    /// Checks that the path template can be used for logging
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !self.path.contains("{channel}") {
+            return Err(format!("Log path has no {{channel}}: {}", self.path));
+        }
+        let path = Path::new(&self.path);
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Log path must be relative: {}", self.path));
+        }
+        Ok(())
+    }
+
+    /// Returns whether logs rotate daily, i.e. their path has a date in it
+    pub fn is_dated(&self) -> bool {
+        ["{yyyy}", "{mm}", "{dd}"]
+            .iter()
+            .any(|p| self.path.contains(p))
+    }
+
+    /// Returns the text log path of a channel on a date, relative to the
+    /// logs directory. network and channel must be escaped with file_name.
+    pub fn render(&self, network: &str, channel: &str, date: NaiveDate) -> PathBuf {
+        let path = self
+            .path
+            .replace("{network}", network)
+            .replace("{channel}", channel)
+            .replace("{yyyy}", &date.format("%Y").to_string())
+            .replace("{mm}", &date.format("%m").to_string())
+            .replace("{dd}", &date.format("%d").to_string());
+        PathBuf::from(path)
+    }
+
+    /// Returns the date a log of channel at path (relative to the logs
+    /// directory) was written on. Returns None if path isn't a dated log of
+    /// the channel. Both text and JSON logs, compressed or not, match.
+    pub fn date_of(&self, network: &str, channel: &str, path: &Path) -> Option<NaiveDate> {
+        let text = self.render_pattern(network, channel);
+        let path = path.to_str()?;
+        let path = path.strip_suffix(".gz").unwrap_or(path);
+        let path = match path.strip_suffix(".jsonl") {
+            Some(stem) => {
+                let text_path = Path::new(&text);
+                let ext = text_path.extension().and_then(|e| e.to_str());
+                format!("{}.{}", stem, ext.unwrap_or_default())
+            }
+            None => path.to_owned(),
+        };
+        match_date(&text, &path)
+    }
+
+    /// Returns the path template with the network and channel filled in
+    fn render_pattern(&self, network: &str, channel: &str) -> String {
+        self.path
+            .replace("{network}", network)
+            .replace("{channel}", channel)
+    }
+
+    /// Returns the deepest directory, relative to the logs directory, that
+    /// holds all dated logs of channel
+    fn base_dir(&self, network: &str, channel: &str) -> PathBuf {
+        static_prefix(&self.render_pattern(network, channel))
+    }
+
+    /// Returns the deepest directory, relative to the logs directory, that
+    /// holds all logs of network. network must be escaped with file_name.
+    pub fn network_dir(&self, network: &str) -> PathBuf {
+        static_prefix(&self.path.replace("{network}", network))
+    }
+
+    /// Returns all dated logs of channel under logs_dir with their dates,
+    /// oldest first
+    pub fn dated_logs(
+        &self,
+        logs_dir: &Path,
+        network: &str,
+        channel: &str,
+    ) -> Vec<(NaiveDate, PathBuf)> {
+        let mut logs = Vec::new();
+        if self.is_dated() {
+            let base = logs_dir.join(self.base_dir(network, channel));
+            walk(&base, &mut |fp| {
+                let rel = fp.strip_prefix(logs_dir).unwrap_or(fp);
+                if let Some(date) = self.date_of(network, channel, rel) {
+                    logs.push((date, fp.to_owned()));
+                }
+            });
+        }
+        logs.sort();
+        logs
+    }
+
+    /// Compresses the dated logs of channel from before current, e.g. those
+    /// closed by a restart across midnight, if logs are compressed
+    pub fn compress_closed(
+        &self,
+        logs_dir: &Path,
+        network: &str,
+        channel: &str,
+        current: NaiveDate,
+    ) -> Result<()> {
+        if !self.compress {
+            return Ok(());
+        }
+        for (date, fp) in self.dated_logs(logs_dir, network, channel) {
+            if date < current && fp.extension().is_none_or(|e| e != "gz") {
+                compress(&fp)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the logs of channel old to those of channel new, e.g. after
+    /// the other party of a query changed nick. Logs new already has are
+    /// merged with in order of time. old and new must be escaped with
+    /// file_name.
+    pub fn move_logs(&self, logs_dir: &Path, network: &str, old: &str, new: &str) -> Result<()> {
+        let mut dates: Vec<_> = if self.is_dated() {
+            let logs = self.dated_logs(logs_dir, network, old);
+            logs.into_iter().map(|(date, _)| date).collect()
+        } else {
+            vec![NaiveDate::default()]
+        };
+        dates.dedup();
+
+        for date in dates {
+            let text = (
+                self.render(network, old, date),
+                self.render(network, new, date),
+            );
+            let json = (
+                text.0.with_extension("jsonl"),
+                text.1.with_extension("jsonl"),
+            );
+            for (from, to) in [text, json] {
+                let (from, to) = (logs_dir.join(from), logs_dir.join(to));
+                for from in [gz_path(&from), from] {
+                    if !from.exists() {
+                        continue;
+                    }
+                    if to.exists() || gz_path(&to).exists() {
+                        merge_log(&to, read_lines(&from)?)?;
+                        fs::remove_file(&from)?;
+                    } else {
+                        let to = match from.extension() {
+                            Some(e) if e == "gz" => gz_path(&to),
+                            _ => to.clone(),
+                        };
+                        if let Some(dir) = to.parent() {
+                            fs::create_dir_all(dir)?;
+                        }
+                        fs::rename(&from, &to)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes dated logs of channel older than the retention period
+    pub fn apply_retention(&self, logs_dir: &Path, network: &str, channel: &str) -> Result<()> {
+        if self.retention_days == 0 {
+            return Ok(());
+        }
+        let oldest = self.timezone.today() - Duration::days(self.retention_days as i64);
+        for (date, fp) in self.dated_logs(logs_dir, network, channel) {
+            if date < oldest {
+                fs::remove_file(fp)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the directories a path template starts with that don't depend
+/// on placeholders
+fn static_prefix(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for part in Path::new(pattern).parent().into_iter().flat_map(Path::iter) {
+        if part.to_str().is_none_or(|p| p.contains('{')) {
+            break;
+        }
+        base.push(part);
+    }
+    base
+}
+
+/// Calls f with every file below dir
+pub fn walk(dir: &Path, f: &mut dyn FnMut(&Path)) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                walk(&path, f);
+            } else {
+                f(&path);
+            }
+        }
+    }
+}
+
+/// Matches s against pattern, where {yyyy}, {mm} and {dd} match 4, 2 and 2
+/// digits, returning the date they spell out
+fn match_date(pattern: &str, s: &str) -> Option<NaiveDate> {
+    let (mut year, mut month, mut day) = (2000, 1, 1);
+    let (mut pattern, mut s) = (pattern, s);
+
+    while !pattern.is_empty() {
+        let placeholder = ["{yyyy}", "{mm}", "{dd}"]
+            .iter()
+            .find(|p| pattern.starts_with(*p));
+        match placeholder {
+            Some(p) => {
+                let width = if *p == "{yyyy}" { 4 } else { 2 };
+                let digits = s.get(..width)?;
+                if !digits.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                let value = digits.parse().ok()?;
+                match *p {
+                    "{yyyy}" => year = value as i32,
+                    "{mm}" => month = value,
+                    _ => day = value,
+                }
+                pattern = &pattern[p.len()..];
+                s = &s[width..];
+            }
+            None => {
+                let c = pattern.chars().next()?;
+                s = s.strip_prefix(c)?;
+                pattern = &pattern[c.len_utf8()..];
+            }
+        }
+    }
+
+    if s.is_empty() {
+        NaiveDate::from_ymd_opt(year, month, day)
+    } else {
+        None
+    }
+}
+
+/// Escapes an id such as a nick, channel or server name so it can be used
+/// as a file name. Path separators, characters that are special in log
+/// path templates or on some filesystems, and a leading dot are replaced
+/// by %XX escapes, so no id can point outside its log directory.
+pub fn file_name(id: &str) -> String {
+    if id.is_empty() {
+        return String::from("%00");
+    }
+
+    let mut name = String::with_capacity(id.len());
+    for (i, c) in id.chars().enumerate() {
+        let special = "/\\:*?\"<>|%{}".contains(c);
+        if special || c.is_control() || (i == 0 && c == '.') {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("%{:02X}", b));
+            }
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Returns the name a server's logs are stored under
+pub fn network_name(server: &str) -> String {
+    file_name(&server.to_ascii_lowercase())
+}
+
+/// Merges lines into the log at fp in order of their timestamps, keeping
+/// compressed logs compressed. Lines already in the log are skipped, as
+/// many times as they occur there, so repeated lines are all imported once.
+/// Returns the indices of the lines that were added.
+pub fn merge_log(fp: &Path, lines: Vec<String>) -> Result<Vec<usize>> {
+    let gz_fp = gz_path(fp);
+    let compressed = gz_fp.exists();
+
+    let existing = if compressed {
+        read_lines(&gz_fp)?
+    } else if fp.exists() {
+        read_lines(fp)?
+    } else {
+        Vec::new()
+    };
+
+    let mut counts: HashMap<_, usize> = HashMap::new();
+    for line in &existing {
+        *counts.entry(line.clone()).or_default() += 1;
+    }
+    let mut added = Vec::new();
+    let mut merged = existing;
+    for (i, line) in lines.into_iter().enumerate() {
+        match counts.get_mut(&line) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => {
+                merged.push(line);
+                added.push(i);
+            }
+        }
+    }
+    if added.is_empty() {
+        return Ok(added);
+    }
+
+    // Lines without a timestamp stay after the line they followed
+    let mut time = String::new();
+    let mut keyed: Vec<_> = merged
+        .into_iter()
+        .map(|line| {
+            if let Some(t) = line_time(&line) {
+                time = t;
+            }
+            (time.clone(), line)
+        })
+        .collect();
+    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if let Some(dir) = fp.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut text = keyed
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    text.push('\n');
+    fs::write(fp, text)?;
+    if compressed {
+        fs::remove_file(&gz_fp)?;
+        compress(fp)?;
+    }
+    Ok(added)
+}
+
+/// Returns the UTC timestamp of a text or JSON log line, which sorts the
+/// same as the time it stands for
+fn line_time(line: &str) -> Option<String> {
+    if line.starts_with('{') {
+        let event: JsonEvent = serde_json::from_str(line).ok()?;
+        return Some(event.timestamp);
+    }
+    let (time, _) = parse_log_line(line);
+    time.map(|t| t.format(LOG_TIME_FORMAT).to_string())
+}
+
+/// Returns the path of the log at fp once compressed
+fn gz_path(fp: &Path) -> PathBuf {
+    let mut gz_fp = fp.as_os_str().to_owned();
+    gz_fp.push(".gz");
+    PathBuf::from(gz_fp)
+}
+
+/// Compresses the file at fp to fp.gz, removing the original
+pub fn compress(fp: &Path) -> Result<()> {
+    let gz_fp = gz_path(fp);
+    let mut input = File::open(fp)?;
+    let mut encoder = GzEncoder::new(File::create(gz_fp)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(fp)
+}
+
+/// Reads all lines of a log as they are, decompressing it if its name ends
+/// in .gz
+pub fn read_lines(fp: &Path) -> Result<Vec<String>> {
+    if fp.extension().is_some_and(|e| e == "gz") {
+        return read_gz_tail(fp, usize::MAX);
+    }
+    let text = fs::read(fp)?;
+    Ok(String::from_utf8_lossy(&text)
+        .lines()
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Reads the last n lines of a gzip compressed log
+pub fn read_gz_tail(fp: &Path, n: usize) -> Result<Vec<String>> {
+    let reader = BufReader::new(GzDecoder::new(File::open(fp)?));
+    let lines = reader.lines().collect::<Result<Vec<_>>>()?;
+    let start = lines.len().saturating_sub(n);
+    Ok(lines[start..].to_vec())
+}
@@ -0,0 +1,111 @@
+/// Quits and rejoins are summed up once none came for this long
+const SUMMARY_DELAY: Duration = Duration::from_secs(5);
+
+/// Rejoins are only told apart from joins for this long after a netsplit
+const REJOIN_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+use crate::connection::CaseMapping;
+use std::time::{Duration, Instant};
+
+/// Returns the servers a quit reason says split, if it is a netsplit's:
+/// two host names such as "*.net *.split"
+pub fn split_servers(reason: &str) -> Option<(&str, &str)> {
+    let (a, b) = reason.split_once(' ')?;
+    let is_host = |s: &str| {
+        s.contains('.')
+            && !s.starts_with('.')
+            && !s.ends_with('.')
+            && !s.contains(char::is_whitespace)
+    };
+    if is_host(a) && is_host(b) {
+        Some((a, b))
+    } else {
+        None
+    }
+}
+
+/// Users that quit when servers split from a network, and rejoined once
+/// they were back
+#[derive(Debug)]
+pub struct Netsplit {
+    pub network: String,
+    pub servers: (String, String),
+    nicks: Vec<String>,           // Everyone who quit
+    quits: Vec<(String, String)>, // Channels and nicks not summed up yet
+    joins: Vec<(String, String)>, // The same for rejoins
+    last: Instant,                // When someone last quit or rejoined
+}
+
+impl Netsplit {
+    pub fn new(network: &str, servers: (&str, &str)) -> Self {
+        Self {
+            network: network.to_owned(),
+            servers: (servers.0.to_owned(), servers.1.to_owned()),
+            nicks: Vec::new(),
+            quits: Vec::new(),
+            joins: Vec::new(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Notes that nick quit from channels
+    pub fn add_quit(&mut self, nick: &str, channels: &[String]) {
+        self.nicks.push(nick.to_owned());
+        for channel in channels {
+            self.quits.push((channel.clone(), nick.to_owned()));
+        }
+        self.last = Instant::now();
+    }
+
+    /// Notes that nick joined channel, returning whether it is back from
+    /// the netsplit
+    pub fn add_join(&mut self, nick: &str, channel: &str, casemapping: CaseMapping) -> bool {
+        if !self.nicks.iter().any(|n| casemapping.is_same(n, nick)) {
+            return false;
+        }
+        self.joins.push((channel.to_owned(), nick.to_owned()));
+        self.last = Instant::now();
+        true
+    }
+
+    /// Returns whether the netsplit is long enough ago to be forgotten
+    pub fn is_over(&self, now: Instant) -> bool {
+        now >= self.last + REJOIN_WINDOW
+    }
+
+    /// Returns the lines summing up the quits and rejoins in each channel,
+    /// once none came for a while
+    pub fn take_summaries(&mut self, now: Instant) -> Vec<(String, String)> {
+        if now < self.last + SUMMARY_DELAY {
+            return Vec::new();
+        }
+        let (a, b) = &self.servers;
+        let mut summaries = Vec::new();
+        for (channel, nicks) in group(std::mem::take(&mut self.quits)) {
+            let line = format!("<-- Netsplit {} <-> {}, quit: {}", a, b, nicks.join(", "));
+            summaries.push((channel, line));
+        }
+        for (channel, nicks) in group(std::mem::take(&mut self.joins)) {
+            let line = format!(
+                "--> Netsplit {} <-> {} over, joined: {}",
+                a,
+                b,
+                nicks.join(", ")
+            );
+            summaries.push((channel, line));
+        }
+        summaries
+    }
+}
+
+/// Groups nicks by channel, keeping the order they came in
+fn group(entries: Vec<(String, String)>) -> Vec<(String, Vec<String>)> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for (channel, nick) in entries {
+        match groups.iter_mut().find(|(c, _)| *c == channel) {
+            Some((_, nicks)) => nicks.push(nick),
+            None => groups.push((channel, vec![nick])),
+        }
+    }
+    groups
+}
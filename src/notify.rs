@@ -0,0 +1,158 @@
+/// Seconds that must pass between two notifications by default
+const DEFAULT_INTERVAL: u64 = 5;
+
+use chrono::NaiveTime;
+use std::io::{Error, Result};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How we are told of highlights and private messages
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotifyMethod {
+    Off,
+    Bell,
+    Osc777,          // Notification many terminals show on the desktop
+    Osc9,            // The same, as iTerm2 and others understand it
+    Command(String), // Run with sh, given the message in MINIRC_* variables
+}
+
+impl FromStr for NotifyMethod {
+    type Err = String;
+
+    /// Parses a method other than running a command, which is set with
+    /// notify.command
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "bell" => Ok(Self::Bell),
+            "osc777" => Ok(Self::Osc777),
+            "osc9" => Ok(Self::Osc9),
+            _ => Err(format!("Unknown notification method: {}", s)),
+        }
+    }
+}
+
+/// A message worth a notification
+#[derive(Debug)]
+pub struct Notification<'a> {
+    pub network: &'a str,
+    pub buffer: &'a str,
+    pub nick: &'a str,
+    pub text: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    pub method: NotifyMethod,
+    pub interval: Duration, // Least time between notifications
+    pub quiet_hours: Option<(NaiveTime, NaiveTime)>, // Local, nothing is notified then
+    last: Option<Instant>,  // When we last notified
+    escapes: Vec<String>,   // Not written to the terminal yet
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new(
+            NotifyMethod::Bell,
+            Duration::from_secs(DEFAULT_INTERVAL),
+            None,
+        )
+    }
+}
+
+impl Notifier {
+    pub fn new(
+        method: NotifyMethod,
+        interval: Duration,
+        quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    ) -> Self {
+        Self {
+            method,
+            interval,
+            quiet_hours,
+            last: None,
+            escapes: Vec::new(),
+        }
+    }
+
+    /// Returns whether time falls in the quiet hours, which may span
+    /// midnight
+    pub fn is_quiet(&self, time: NaiveTime) -> bool {
+        match self.quiet_hours {
+            Some((start, end)) if start <= end => start <= time && time < end,
+            Some((start, end)) => start <= time || time < end,
+            None => false,
+        }
+    }
+
+    /// Notifies of n unless it is off, quiet hours or too soon after the
+    /// last notification. Returns whether it did. Notifications by the
+    /// terminal are kept for the UI thread, see take_escapes.
+    pub fn notify(&mut self, n: &Notification<'_>, time: NaiveTime) -> Result<bool> {
+        let now = Instant::now();
+        let too_soon = self.last.is_some_and(|last| now < last + self.interval);
+        if self.method == NotifyMethod::Off || too_soon || self.is_quiet(time) {
+            return Ok(false);
+        }
+        self.last = Some(now);
+
+        let text = format!("<{}> {}", n.nick, n.text);
+        let escape = match self.method {
+            NotifyMethod::Off => return Ok(false),
+            NotifyMethod::Bell => String::from("\x07"),
+            NotifyMethod::Osc777 => format!(
+                "\x1b]777;notify;{};{}\x07",
+                sanitize(n.buffer).replace(';', ","),
+                sanitize(&text)
+            ),
+            NotifyMethod::Osc9 => format!("\x1b]9;{}: {}\x07", sanitize(n.buffer), sanitize(&text)),
+            NotifyMethod::Command(ref command) => {
+                run_command(command, n)?;
+                return Ok(true);
+            }
+        };
+        self.escapes.push(escape);
+        Ok(true)
+    }
+
+    /// Returns the escape sequences to write to the terminal for the
+    /// notifications since the last call
+    pub fn take_escapes(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.escapes)
+    }
+}
+
+/// Parses quiet hours given as HH:MM-HH:MM
+pub fn parse_quiet_hours(s: &str) -> std::result::Result<(NaiveTime, NaiveTime), String> {
+    let invalid = || format!("Invalid hours, expected HH:MM-HH:MM: {}", s);
+    let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| invalid());
+    Ok((parse(start)?, parse(end)?))
+}
+
+/// Removes what would end or garble a terminal escape sequence
+fn sanitize(s: &str) -> String {
+    s.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Runs command with sh without waiting for it, passing the notification
+/// in the environment
+fn run_command(command: &str, n: &Notification<'_>) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("MINIRC_NETWORK", n.network)
+        .env("MINIRC_BUFFER", n.buffer)
+        .env("MINIRC_NICK", n.nick)
+        .env("MINIRC_TEXT", n.text)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", command, e)))?;
+    // Reaps it once it is done
+    thread::spawn(move || child.wait());
+    Ok(())
+}
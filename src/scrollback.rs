@@ -0,0 +1,272 @@
+const SCROLLBACK_SIZE: usize = 2000;
+
+use chrono::{DateTime, Local, Utc};
+use regex::Regex;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Normal,    // Messages
+    Info,      // Messages from the client itself
+    Dim,       // Lines from the log shown as backlog
+    Highlight, // Messages highlighting us
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub time: Option<DateTime<Utc>>,
+    pub text: String,
+    pub style: Style,
+}
+
+impl Line {
+    /// Creates a line timestamped with the current time
+    pub fn new(text: &str, style: Style) -> Self {
+        Self::with_time(Some(Utc::now()), text, style)
+    }
+
+    pub fn with_time(time: Option<DateTime<Utc>>, text: &str, style: Style) -> Self {
+        let text = text.trim_end().to_owned();
+        Self { time, text, style }
+    }
+
+    /// Returns the line as displayed, prefixed with its local time in the
+    /// strftime-style format fmt. An empty fmt disables the prefix.
+    pub fn display(&self, fmt: &str) -> String {
+        match self.time {
+            Some(time) if !fmt.is_empty() => {
+                let time = time.with_timezone(&Local).format(fmt);
+                format!("{} {}", time, self.text)
+            }
+            _ => self.text.clone(),
+        }
+    }
+
+    /// Returns whether the line is found when searching for regex
+    fn is_searchable(&self, regex: &Regex) -> bool {
+        self.style != Style::Info && regex.is_match(&self.text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scroll {
+    Up(usize),   // Towards older lines
+    Down(usize), // Towards newer lines
+    Top,
+    Bottom,
+}
+
+/// The rows to display for a scrollback, already wrapped to the window width
+#[derive(Debug, PartialEq)]
+pub struct View {
+    pub rows: Vec<(String, Style)>,
+    pub more: usize, // Number of lines below the view
+}
+
+/// Lines of a scrollback matching a search
+#[derive(Debug)]
+struct Search {
+    regex: Regex,
+    matches: Vec<usize>,    // Indices of matching lines, oldest first
+    current: Option<usize>, // Index into matches of the one jumped to
+}
+
+#[derive(Debug, Default)]
+pub struct Scrollback {
+    lines: VecDeque<Line>,
+    offset: usize, // Number of lines scrolled up from the bottom
+    search: Option<Search>,
+}
+
+impl Scrollback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of lines in the scrollback
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Returns whether the scrollback has no lines
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Returns whether the view is scrolled up from the newest line
+    pub fn is_scrolled(&self) -> bool {
+        self.offset > 0
+    }
+
+    /// Appends a line, dropping the oldest one if the scrollback is full.
+    /// While scrolled up, the view stays on the lines it's showing.
+    pub fn push(&mut self, line: Line) {
+        if self.lines.len() >= SCROLLBACK_SIZE {
+            self.lines.pop_front();
+            if let Some(ref mut search) = self.search {
+                search.shift();
+            }
+        }
+        if let Some(ref mut search) = self.search {
+            if line.is_searchable(&search.regex) {
+                search.matches.push(self.lines.len());
+            }
+        }
+        self.lines.push_back(line);
+        if self.offset > 0 {
+            self.offset = (self.offset + 1).min(self.lines.len() - 1);
+        }
+    }
+
+    /// Scrolls the view
+    pub fn scroll(&mut self, scroll: Scroll) {
+        let max = self.lines.len().saturating_sub(1);
+        self.offset = match scroll {
+            Scroll::Up(n) => (self.offset + n).min(max),
+            Scroll::Down(n) => self.offset.saturating_sub(n),
+            Scroll::Top => max,
+            Scroll::Bottom => 0,
+        };
+    }
+
+    /// Starts a search for lines matching regex, replacing any previous
+    /// one. Returns the number of matching lines. Messages from the client
+    /// itself aren't searched.
+    pub fn search(&mut self, regex: Regex) -> usize {
+        let matches: Vec<_> = (self.lines.iter().enumerate())
+            .filter(|(_, line)| line.is_searchable(&regex))
+            .map(|(i, _)| i)
+            .collect();
+        let count = matches.len();
+        self.search = Some(Search {
+            regex,
+            matches,
+            current: None,
+        });
+        count
+    }
+
+    /// Scrolls to the next older or newer match of the search, wrapping
+    /// around at either end. Returns the match's number, counting from the
+    /// newest, and the number of matches.
+    pub fn next_match(&mut self, older: bool) -> Option<(usize, usize)> {
+        let search = self.search.as_mut()?;
+        let count = search.matches.len();
+        if count == 0 {
+            return None;
+        }
+
+        let current = match (search.current, older) {
+            (None, true) | (Some(0), true) => count - 1,
+            (Some(i), true) => i - 1,
+            (Some(i), false) if i + 1 < count => i + 1,
+            (_, false) => 0,
+        };
+        search.current = Some(current);
+        self.offset = self.lines.len() - 1 - search.matches[current];
+        Some((count - current, count))
+    }
+
+    /// Ends the search
+    pub fn clear_search(&mut self) {
+        self.search = None;
+    }
+
+    /// Returns the regex being searched for, if any
+    pub fn search_regex(&self) -> Option<&Regex> {
+        self.search.as_ref().map(|search| &search.regex)
+    }
+
+    /// Returns the rows to display in a window of the given size, with
+    /// lines timestamped according to fmt
+    pub fn view(&self, width: usize, height: usize, fmt: &str) -> View {
+        let end = self.lines.len() - self.offset;
+        let mut rows = VecDeque::with_capacity(height); // Line index, row
+
+        for (i, line) in self.lines.range(..end).enumerate().rev() {
+            if rows.len() >= height {
+                break;
+            }
+            for row in wrap(&line.display(fmt), width).into_iter().rev() {
+                rows.push_front((i, row));
+            }
+        }
+
+        // Near the top there may not be enough older lines to fill the view
+        let filled_below = rows.len() < height;
+        let mut next = end;
+        while rows.len() < height && next < self.lines.len() {
+            for row in wrap(&self.lines[next].display(fmt), width) {
+                rows.push_back((next, row));
+            }
+            next += 1;
+        }
+
+        // Leave the last row for the "more" marker
+        let height = if next < self.lines.len() {
+            height.saturating_sub(1)
+        } else {
+            height
+        };
+        while rows.len() > height {
+            if filled_below {
+                rows.pop_back();
+            } else {
+                rows.pop_front();
+            }
+        }
+
+        let last = rows.back().map(|(i, _)| *i + 1).unwrap_or(end);
+        View {
+            more: self.lines.len() - last,
+            rows: rows
+                .into_iter()
+                .map(|(i, row)| (row, self.lines[i].style))
+                .collect(),
+        }
+    }
+}
+
+impl Search {
+    /// Updates the matches after the oldest line was dropped
+    fn shift(&mut self) {
+        if self.matches.first() == Some(&0) {
+            self.matches.remove(0);
+            self.current = self.current.and_then(|i| i.checked_sub(1));
+        }
+        for i in self.matches.iter_mut() {
+            *i -= 1;
+        }
+    }
+}
+
+/// Splits s into rows shorter than width, breaking between words where
+/// possible
+pub fn wrap(s: &str, width: usize) -> Vec<String> {
+    let width = width.max(2) - 1;
+    let mut rows = Vec::new();
+    let mut row = String::with_capacity(width);
+
+    for word in s.split(' ') {
+        let word_len = word.chars().count();
+        let row_len = row.chars().count();
+        if row_len > 0 && row_len + 1 + word_len <= width {
+            row.push(' ');
+        } else if row_len > 0 {
+            rows.push(row);
+            row = String::with_capacity(width);
+        }
+
+        // Words longer than a row get broken up
+        for c in word.chars() {
+            if row.chars().count() >= width {
+                rows.push(row);
+                row = String::with_capacity(width);
+            }
+            row.push(c);
+        }
+    }
+
+    rows.push(row);
+    rows
+}
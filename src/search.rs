@@ -0,0 +1,174 @@
+const MAX_HITS: usize = 1000;
+
+use crate::channel::JsonEvent;
+use crate::logfile::{read_lines, walk};
+use crate::store::Store;
+use flate2::read::GzDecoder;
+use regex::{Regex, RegexBuilder};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+/// Where a search hit was found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Log(PathBuf, usize), // A log, and the index of the line in it
+    Store(i64),          // The SQLite store, and the id of the event
+}
+
+/// A logged event matching a search
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub source: Source,
+    pub label: String, // Log path relative to the searched directory, or buffer
+    pub text: String,  // As written to the text log
+}
+
+impl Hit {
+    /// Returns where the hit is, as shown above its context
+    pub fn location(&self) -> String {
+        match self.source {
+            Source::Log(_, line) => format!("{}, line {}", self.label, line + 1),
+            Source::Store(_) => self.label.clone(),
+        }
+    }
+}
+
+/// Returns a case-insensitive regex matching pattern, which is taken
+/// literally unless is_regex is set
+pub fn build_regex(pattern: &str, is_regex: bool) -> std::result::Result<Regex, String> {
+    let pattern = if is_regex {
+        pattern.to_owned()
+    } else {
+        regex::escape(pattern)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Returns whether fp is a JSON log, compressed or not
+pub fn is_json_log(fp: &Path) -> bool {
+    let name = fp.to_string_lossy();
+    name.ends_with(".jsonl") || name.ends_with(".jsonl.gz")
+}
+
+/// Reads all lines of a log in the text log's format. JSON logs are
+/// converted and compressed logs decompressed.
+pub fn read_log(fp: &Path) -> Result<Vec<String>> {
+    let lines = read_lines(fp)?;
+    if !is_json_log(fp) {
+        return Ok(lines);
+    }
+    Ok(lines
+        .iter()
+        .filter_map(|l| serde_json::from_str::<JsonEvent>(l).ok())
+        .map(|event| event.to_log_line())
+        .collect())
+}
+
+/// Opens a log to read line by line, decompressing it if its name ends in
+/// .gz
+fn open_log(fp: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(fp)?;
+    if fp.extension().is_some_and(|e| e == "gz") {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Searches the logs below dir for lines matching regex, returning the last
+/// MAX_HITS, oldest log first. Logs are read a line at a time. Only JSON
+/// logs are searched if json is set, otherwise only text logs.
+pub fn search_logs(dir: &Path, regex: &Regex, json: bool) -> Vec<Hit> {
+    let mut logs = Vec::new();
+    walk(dir, &mut |fp| {
+        let name = fp.to_string_lossy();
+        let is_log = !name.ends_with(".db") && !name.contains(".db-");
+        if is_log && is_json_log(fp) == json {
+            logs.push(fp.to_owned());
+        }
+    });
+    logs.sort();
+
+    let mut hits = VecDeque::new();
+    for fp in logs {
+        // Logs that can't be read just don't turn up
+        let mut reader = match open_log(&fp) {
+            Ok(reader) => reader,
+            Err(_) => continue,
+        };
+        let label = fp.strip_prefix(dir).unwrap_or(&fp).to_string_lossy();
+        let (mut buf, mut i) = (Vec::new(), 0);
+        while reader.read_until(b'\n', &mut buf).unwrap_or_default() > 0 {
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            // Lines are numbered as read_log returns them
+            let text = if json {
+                serde_json::from_str::<JsonEvent>(line)
+                    .ok()
+                    .map(|event| event.to_log_line())
+            } else {
+                Some(line.to_owned())
+            };
+            if let Some(text) = text {
+                if regex.is_match(&text) {
+                    // Keep the newest hits
+                    if hits.len() == MAX_HITS {
+                        hits.pop_front();
+                    }
+                    hits.push_back(Hit {
+                        source: Source::Log(fp.clone(), i),
+                        label: label.to_string(),
+                        text,
+                    });
+                }
+                i += 1;
+            }
+            buf.clear();
+        }
+    }
+    hits.into()
+}
+
+/// Searches the events of network in the SQLite store, returning the last
+/// MAX_HITS that regex matches, oldest first. Events are matched as their
+/// log lines, like search_logs does.
+pub fn search_store(store: &Store, network: &str, regex: &Regex) -> Result<Vec<Hit>> {
+    let events = store.find(network, MAX_HITS, |event| {
+        regex.is_match(&event.to_log_line())
+    })?;
+    Ok(events
+        .into_iter()
+        .map(|(id, event)| Hit {
+            source: Source::Store(id),
+            text: event.to_log_line(),
+            label: event.buffer,
+        })
+        .collect())
+}
+
+/// Returns up to n lines before and after the hit, and the index of the
+/// hit among them. Hits in the SQLite store are read from store.
+pub fn read_context(hit: &Hit, n: usize, store: Option<&Store>) -> Result<(Vec<String>, usize)> {
+    let (fp, line) = match hit.source {
+        Source::Log(ref fp, line) => (fp, line),
+        Source::Store(id) => {
+            let store = store.ok_or_else(|| Error::new(ErrorKind::NotFound, "No SQLite store"))?;
+            let (events, pos) = store.context(id, n)?;
+            return Ok((events.iter().map(JsonEvent::to_log_line).collect(), pos));
+        }
+    };
+    let lines = read_log(fp)?;
+    if line >= lines.len() {
+        let msg = "Log changed since it was searched";
+        return Err(Error::new(ErrorKind::InvalidData, msg));
+    }
+    let start = line.saturating_sub(n);
+    let end = (line + n + 1).min(lines.len());
+    Ok((lines[start..end].to_vec(), line - start))
+}
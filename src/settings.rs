@@ -0,0 +1,41 @@
+use crate::highlight::Highlights;
+use crate::history::HistoryMode;
+use crate::ignore::Ignore;
+use crate::logfile::LogConfig;
+use crate::notify::Notifier;
+use chrono::format::{Item, StrftimeItems};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct Settings {
+    pub history_mode: HistoryMode,
+    pub backlog_lines: usize,
+    pub timestamp_format: String, // strftime-style, empty for none
+    pub log: LogConfig,
+    pub highlights: Highlights,
+    pub notifier: Notifier,
+    pub ignores: Vec<Ignore>,
+    pub smart_filter: Option<Duration>, // Hides joins and such of users quiet for longer
+    pub overrides: Vec<&'static str>,   // Configuration keys set on the command line
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            history_mode: HistoryMode::PerBuffer,
+            backlog_lines: 20,
+            timestamp_format: String::from("%H:%M"),
+            log: LogConfig::default(),
+            highlights: Highlights::default(),
+            notifier: Notifier::default(),
+            ignores: Vec::new(),
+            smart_filter: None,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// Returns whether fmt is a strftime-style format chrono can display
+pub fn is_valid_time_format(fmt: &str) -> bool {
+    StrftimeItems::new(fmt).all(|item| item != Item::Error)
+}
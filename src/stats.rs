@@ -0,0 +1,339 @@
+const TOP: usize = 10;
+const MIN_WORD_LEN: usize = 4; // Shorter words are mostly "the" and "and"
+const BAR_WIDTH: usize = 40;
+
+const STYLE: &str = "
+body { font-family: sans-serif; background: #fdfdfd; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 2px 8px; text-align: left; }
+th { background: #e4e8ef; }
+tr:nth-child(even) { background: #f2f4f7; }
+.bar { background: #5b6bbf; height: 1em; }
+";
+
+use crate::channel::JsonEvent;
+use crate::connection::CaseMapping;
+use crate::export::{escape_html, event_time, read_events, strip_formatting};
+use crate::logfile::{file_name, network_name, LogConfig, LogTimezone};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "html" => Ok(Self::Html),
+            _ => Err(format!("Unknown report format: {}", s)),
+        }
+    }
+}
+
+/// What to report on, as given to the stats command
+#[derive(Debug)]
+pub struct StatsOptions {
+    pub format: ReportFormat,
+    pub network: String,
+    pub channel: String,
+    pub output: Option<PathBuf>, // Where to write the report, else stdout
+}
+
+/// What a nick did in a channel
+#[derive(Debug, Default, Clone)]
+pub struct UserStats {
+    pub messages: usize,
+    pub words: usize,
+    pub first_seen: Option<NaiveDateTime>, // In the log timezone
+    pub last_seen: Option<NaiveDateTime>,
+}
+
+/// Statistics of a channel's log
+#[derive(Debug)]
+pub struct Stats {
+    pub users: HashMap<String, UserStats>,
+    pub hours: [usize; 24],               // Messages by hour of day
+    pub days: BTreeMap<NaiveDate, usize>, // Messages by day
+    pub words: HashMap<String, usize>,    // Uses of each word
+    pub longest: Vec<(String, String)>,   // Nick and line, longest first
+    pub topics: Vec<(NaiveDateTime, String, String)>, // Time, setter and topic
+}
+
+impl Stats {
+    /// Gathers the statistics of events, with times in timezone
+    pub fn new(events: &[JsonEvent], timezone: LogTimezone) -> Self {
+        let mut stats = Self {
+            users: HashMap::new(),
+            hours: [0; 24],
+            days: BTreeMap::new(),
+            words: HashMap::new(),
+            longest: Vec::new(),
+            topics: Vec::new(),
+        };
+
+        for event in events {
+            let (time, nick) = match (event_time(event), &event.nick) {
+                (Some(time), Some(nick)) => (timezone.local_time(time), nick),
+                _ => continue,
+            };
+            let user = stats.users.entry(nick.clone()).or_default();
+            user.first_seen = Some(user.first_seen.map_or(time, |t| t.min(time)));
+            user.last_seen = Some(user.last_seen.map_or(time, |t| t.max(time)));
+
+            match event.kind.as_str() {
+                "privmsg" => {
+                    let text = strip_formatting(event.action().unwrap_or(&event.text));
+                    let words: Vec<_> = text
+                        .split_whitespace()
+                        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+                        .filter(|w| !w.is_empty())
+                        .collect();
+                    user.messages += 1;
+                    user.words += words.len();
+                    for word in words.iter().filter(|w| w.chars().count() >= MIN_WORD_LEN) {
+                        *stats.words.entry(word.to_lowercase()).or_default() += 1;
+                    }
+                    stats.hours[time.hour() as usize] += 1;
+                    *stats.days.entry(time.date()).or_default() += 1;
+                    stats.longest.push((nick.clone(), text));
+                }
+                "topic" => stats.topics.push((time, nick.clone(), event.text.clone())),
+                _ => (),
+            }
+        }
+
+        stats
+            .longest
+            .sort_by_key(|(_, text)| std::cmp::Reverse(text.chars().count()));
+        stats.longest.truncate(TOP);
+        stats
+    }
+
+    /// Returns the number of messages sent
+    pub fn messages(&self) -> usize {
+        self.days.values().sum()
+    }
+
+    /// Returns the nicks seen, most messages first
+    pub fn top_users(&self) -> Vec<(&str, &UserStats)> {
+        let mut users: Vec<_> = self.users.iter().map(|(n, u)| (n.as_str(), u)).collect();
+        users.sort_by(|a, b| b.1.messages.cmp(&a.1.messages).then(a.0.cmp(b.0)));
+        users
+    }
+
+    /// Returns the TOP most used words with their number of uses
+    pub fn top_words(&self) -> Vec<(&str, usize)> {
+        let mut words: Vec<_> = self.words.iter().map(|(w, n)| (w.as_str(), *n)).collect();
+        words.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        words.truncate(TOP);
+        words
+    }
+}
+
+/// Reports on a channel's whole log as options ask for, with the channel
+/// name folded by casemapping as when it was logged
+pub fn stats(
+    options: &StatsOptions,
+    log: &LogConfig,
+    logs_dir: &Path,
+    casemapping: CaseMapping,
+) -> Result<String> {
+    let network = network_name(&options.network);
+    let channel = file_name(&casemapping.fold(&options.channel));
+    let events = read_events(log, logs_dir, (&network, &channel), None, None)?;
+    let stats = Stats::new(&events, log.timezone);
+
+    let title = format!("Statistics of {} on {}", options.channel, options.network);
+    Ok(match options.format {
+        ReportFormat::Text => render_text(&stats, &title),
+        ReportFormat::Html => render_html(&stats, &title),
+    })
+}
+
+/// Returns a bar n / max of BAR_WIDTH long
+fn bar(n: usize, max: usize) -> usize {
+    (n * BAR_WIDTH).checked_div(max).unwrap_or_default()
+}
+
+fn format_seen(time: Option<NaiveDateTime>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Returns a summary of when the log starts and ends
+fn summary(stats: &Stats) -> String {
+    let mut summary = format!(
+        "{} messages from {} nicks",
+        stats.messages(),
+        stats.users.len()
+    );
+    let days = &stats.days;
+    if let (Some(first), Some(last)) = (days.keys().next(), days.keys().next_back()) {
+        let _ = write!(summary, ", {} to {}", first, last);
+    }
+    summary
+}
+
+/// Renders statistics as plain text
+pub fn render_text(stats: &Stats, title: &str) -> String {
+    let mut text = format!("{}\n{}\n", title, summary(stats));
+
+    text.push_str("\nMost active nicks\n");
+    let _ = writeln!(
+        text,
+        "  {:<16} {:>8} {:>8}  {:<16}  {:<16}",
+        "nick", "messages", "words", "first seen", "last seen"
+    );
+    for (nick, user) in stats.top_users() {
+        let _ = writeln!(
+            text,
+            "  {:<16} {:>8} {:>8}  {:<16}  {:<16}",
+            nick,
+            user.messages,
+            user.words,
+            format_seen(user.first_seen),
+            format_seen(user.last_seen)
+        );
+    }
+
+    text.push_str("\nActivity by hour\n");
+    let max = stats.hours.iter().copied().max().unwrap_or_default();
+    for (hour, n) in stats.hours.iter().enumerate() {
+        let _ = writeln!(
+            text,
+            "  {:02} {:<w$} {}",
+            hour,
+            "#".repeat(bar(*n, max)),
+            n,
+            w = BAR_WIDTH
+        );
+    }
+
+    text.push_str("\nActivity by day\n");
+    let max = stats.days.values().copied().max().unwrap_or_default();
+    for (day, n) in &stats.days {
+        let _ = writeln!(
+            text,
+            "  {} {:<w$} {}",
+            day,
+            "#".repeat(bar(*n, max)),
+            n,
+            w = BAR_WIDTH
+        );
+    }
+
+    text.push_str("\nMost used words\n");
+    for (word, n) in stats.top_words() {
+        let _ = writeln!(text, "  {:<24} {}", word, n);
+    }
+
+    text.push_str("\nLongest lines\n");
+    for (nick, line) in &stats.longest {
+        let _ = writeln!(text, "  <{}> {}", nick, line);
+    }
+
+    text.push_str("\nTopics\n");
+    for (time, nick, topic) in &stats.topics {
+        let _ = writeln!(
+            text,
+            "  {} {}: {}",
+            format_seen(Some(*time)),
+            nick,
+            strip_formatting(topic)
+        );
+    }
+    text
+}
+
+/// Renders statistics as a standalone HTML page
+pub fn render_html(stats: &Stats, title: &str) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n<p>{2}</p>\n",
+        escape_html(title),
+        STYLE,
+        escape_html(&summary(stats))
+    );
+
+    html.push_str(
+        "<h2>Most active nicks</h2>\n<table>\n<tr><th>Nick</th><th>Messages</th>\
+         <th>Words</th><th>First seen</th><th>Last seen</th></tr>\n",
+    );
+    for (nick, user) in stats.top_users() {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(nick),
+            user.messages,
+            user.words,
+            format_seen(user.first_seen),
+            format_seen(user.last_seen)
+        );
+    }
+    html.push_str("</table>\n");
+
+    let bar_row = |label: String, n: usize, max: usize| {
+        format!(
+            "<tr><td>{}</td><td><div class=\"bar\" style=\"width: {}em\"></div></td><td>{}</td></tr>\n",
+            label,
+            bar(n, max) as f32 / 2.0,
+            n
+        )
+    };
+    html.push_str("<h2>Activity by hour</h2>\n<table>\n");
+    let max = stats.hours.iter().copied().max().unwrap_or_default();
+    for (hour, n) in stats.hours.iter().enumerate() {
+        html.push_str(&bar_row(format!("{:02}:00", hour), *n, max));
+    }
+    html.push_str("</table>\n<h2>Activity by day</h2>\n<table>\n");
+    let max = stats.days.values().copied().max().unwrap_or_default();
+    for (day, n) in &stats.days {
+        html.push_str(&bar_row(day.to_string(), *n, max));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Most used words</h2>\n<table>\n<tr><th>Word</th><th>Uses</th></tr>\n");
+    for (word, n) in stats.top_words() {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(word),
+            n
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Longest lines</h2>\n<table>\n<tr><th>Nick</th><th>Line</th></tr>\n");
+    for (nick, line) in &stats.longest {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(nick),
+            escape_html(line)
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Topics</h2>\n<table>\n<tr><th>Set</th><th>By</th><th>Topic</th></tr>\n");
+    for (time, nick, topic) in &stats.topics {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            format_seen(Some(*time)),
+            escape_html(nick),
+            escape_html(&strip_formatting(topic))
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
@@ -0,0 +1,225 @@
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id         INTEGER PRIMARY KEY,
+        timestamp  TEXT NOT NULL,
+        network    TEXT NOT NULL,
+        buffer     TEXT NOT NULL,
+        buffer_key TEXT NOT NULL,
+        kind       TEXT NOT NULL,
+        nick       TEXT,
+        hostmask   TEXT,
+        text       TEXT NOT NULL,
+        tags       TEXT NOT NULL,
+        msgid      TEXT
+    );
+    CREATE INDEX IF NOT EXISTS events_by_buffer
+        ON events (network, buffer_key, timestamp);
+    CREATE VIRTUAL TABLE IF NOT EXISTS events_fts
+        USING fts5 (text, content = 'events', content_rowid = 'id');
+    CREATE TRIGGER IF NOT EXISTS events_fts_insert AFTER INSERT ON events BEGIN
+        INSERT INTO events_fts (rowid, text) VALUES (new.id, new.text);
+    END;
+    CREATE TRIGGER IF NOT EXISTS events_fts_delete AFTER DELETE ON events BEGIN
+        INSERT INTO events_fts (events_fts, rowid, text)
+            VALUES ('delete', old.id, old.text);
+    END;
+";
+
+const FIND_PAGE: i64 = 1000; // Events read at once when searching
+
+const COLUMNS: &str = "timestamp, network, buffer, kind, nick, hostmask, text, tags, msgid";
+
+use crate::channel::JsonEvent;
+use rusqlite::{params, Connection, Row};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A SQLite database of logged events with a full-text index of their text.
+/// One is shared by all buffers logged under the same directory.
+#[derive(Debug)]
+pub struct Store {
+    db: Mutex<Connection>,
+}
+
+impl Store {
+    /// Opens the store at fp, creating it if it doesn't exist
+    pub fn open(fp: &Path) -> Result<Self> {
+        let db = Connection::open(fp).map_err(Error::other)?;
+        db.execute_batch(SCHEMA).map_err(Error::other)?;
+        Ok(Self { db: Mutex::new(db) })
+    }
+
+    /// Records an event of the buffer with the casefolded name key
+    pub fn insert(&self, event: &JsonEvent, key: &str) -> Result<()> {
+        let tags = serde_json::to_string(&event.tags)?;
+        self.db
+            .lock()
+            .unwrap()
+            .execute(
+                &format!(
+                    "INSERT INTO events ({}, buffer_key)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    COLUMNS
+                ),
+                params![
+                    event.timestamp,
+                    event.network,
+                    event.buffer,
+                    event.kind,
+                    event.nick,
+                    event.hostmask,
+                    event.text,
+                    tags,
+                    event.msgid,
+                    key,
+                ],
+            )
+            .map_err(Error::other)?;
+        Ok(())
+    }
+
+    /// Moves the events of the buffer with the casefolded name old to the
+    /// buffer named buffer, with the casefolded name new
+    pub fn rename(&self, network: &str, old: &str, new: &str, buffer: &str) -> Result<()> {
+        self.db
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE events SET buffer = ?4, buffer_key = ?3
+                 WHERE network = ?1 AND buffer_key = ?2",
+                params![network, old, new, buffer],
+            )
+            .map_err(Error::other)?;
+        Ok(())
+    }
+
+    /// Returns the last n events of a buffer, oldest first
+    pub fn tail(&self, network: &str, key: &str, n: usize) -> Result<Vec<JsonEvent>> {
+        let sql = format!(
+            "SELECT {} FROM (
+                 SELECT * FROM events WHERE network = ?1 AND buffer_key = ?2
+                 ORDER BY id DESC LIMIT ?3
+             ) ORDER BY id",
+            COLUMNS
+        );
+        self.query(&sql, params![network, key, n as i64])
+    }
+
+    /// Returns the last n events matching the full-text query with their
+    /// ids, oldest first. Only events of the buffer with name key are
+    /// searched if one is given.
+    pub fn search(
+        &self,
+        network: &str,
+        key: Option<&str>,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(i64, JsonEvent)>> {
+        let sql = format!(
+            "SELECT id, {} FROM (
+                 SELECT events.* FROM events_fts JOIN events ON events.id = events_fts.rowid
+                 WHERE events_fts MATCH ?1 AND network = ?2
+                     AND (?3 IS NULL OR buffer_key = ?3)
+                 ORDER BY events.id DESC LIMIT ?4
+             ) ORDER BY id",
+            COLUMNS
+        );
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&sql).map_err(Error::other)?;
+        let rows = stmt
+            .query_map(params![query, network, key, n as i64], |row| {
+                Ok((row.get(0)?, from_row(row, 1)?))
+            })
+            .map_err(Error::other)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(Error::other)
+    }
+
+    /// Returns the last n events of network that matches holds for with
+    /// their ids, oldest first. Events are read in pages, so others can be
+    /// stored while searching.
+    pub fn find(
+        &self,
+        network: &str,
+        n: usize,
+        mut matches: impl FnMut(&JsonEvent) -> bool,
+    ) -> Result<Vec<(i64, JsonEvent)>> {
+        let sql = format!(
+            "SELECT id, {} FROM events WHERE network = ?1 AND id < ?2
+             ORDER BY id DESC LIMIT ?3",
+            COLUMNS
+        );
+        let (mut found, mut before) = (Vec::new(), i64::MAX);
+        while found.len() < n {
+            let db = self.db.lock().unwrap();
+            let mut stmt = db.prepare_cached(&sql).map_err(Error::other)?;
+            let page = stmt
+                .query_map(params![network, before, FIND_PAGE], |row| {
+                    Ok((row.get(0)?, from_row(row, 1)?))
+                })
+                .map_err(Error::other)?
+                .collect::<rusqlite::Result<Vec<(i64, JsonEvent)>>>()
+                .map_err(Error::other)?;
+            drop(stmt);
+            drop(db);
+            match page.last() {
+                Some((id, _)) => before = *id,
+                None => break,
+            }
+            found.extend(page.into_iter().filter(|(_, event)| matches(event)));
+        }
+        found.truncate(n);
+        found.reverse();
+        Ok(found)
+    }
+
+    /// Returns up to n events of the same buffer before and after the event
+    /// with id, and the index of that event among them
+    pub fn context(&self, id: i64, n: usize) -> Result<(Vec<JsonEvent>, usize)> {
+        let same_buffer = "(network, buffer_key) =
+            (SELECT network, buffer_key FROM events WHERE id = ?1)";
+        let sql = format!(
+            "SELECT {} FROM events WHERE {} AND id < ?1 ORDER BY id DESC LIMIT ?2",
+            COLUMNS, same_buffer
+        );
+        let mut events = self.query(&sql, params![id, n as i64])?;
+        events.reverse();
+        let pos = events.len();
+        let sql = format!(
+            "SELECT {} FROM events WHERE {} AND id >= ?1 ORDER BY id LIMIT ?2",
+            COLUMNS, same_buffer
+        );
+        let after = self.query(&sql, params![id, n as i64 + 1])?;
+        if after.is_empty() {
+            let msg = "Event no longer stored";
+            return Err(Error::new(ErrorKind::NotFound, msg));
+        }
+        events.extend(after);
+        Ok((events, pos))
+    }
+
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<JsonEvent>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(sql).map_err(Error::other)?;
+        let rows = stmt
+            .query_map(params, |row| from_row(row, 0))
+            .map_err(Error::other)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(Error::other)
+    }
+}
+
+/// Reads an event from a row holding COLUMNS from index start on
+fn from_row(row: &Row<'_>, start: usize) -> rusqlite::Result<JsonEvent> {
+    let tags: String = row.get(start + 7)?;
+    Ok(JsonEvent {
+        timestamp: row.get(start)?,
+        network: row.get(start + 1)?,
+        buffer: row.get(start + 2)?,
+        kind: row.get(start + 3)?,
+        nick: row.get(start + 4)?,
+        hostmask: row.get(start + 5)?,
+        text: row.get(start + 6)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        msgid: row.get(start + 8)?,
+    })
+}
@@ -1,90 +1,494 @@
-use crate::channel::Channel;
-use crate::command::Command;
+/// Character user commands start with
+pub const COMMAND_PREFIX: char = ':';
+
+/// Names of user commands. Commands may be abbreviated to any prefix of
+/// their name, earlier entries winning if that is ambiguous.
+pub const COMMANDS: &[&str] = &[
+    "quit",
+    "join",
+    "part",
+    "channel",
+    "search",
+    "context",
+    "export",
+    "reload",
+    "connect",
+    "disconnect",
+    "query",
+    "ignore",
+    "unignore",
+];
+
+/// What can't be configured yet, as told on reloading the configuration
+const UNCONFIGURABLE: &[&str] = &["aliases", "colours", "keybindings"];
+
+/// Names of the buffers search results are shown in
+const SEARCH_BUFFER: &str = "*search*";
+const CONTEXT_BUFFER: &str = "*context*";
+
+/// Number of lines shown before and after a search result in context
+const CONTEXT_LINES: usize = 10;
+
+/// How :ignore is used
+const IGNORE_USAGE: &str =
+    "Usage: :ignore [-t=messages,notices,ctcp,joins,nicks] [-c=#channel,...] [-e=30m|2h|7d] <mask>";
+
+/// Prefixes marking channel privileges in NAMES replies
+const NICK_PREFIXES: &[char] = &['~', '&', '@', '%', '+'];
+
+use crate::argparse::parse_date;
+use crate::channel::{parse_log_line, Event};
+use crate::command::{join_line, sasl_plain, send_auth, Command, Meta};
+use crate::config::ConfigDiff;
+use crate::connection::Connection;
+use crate::export::ExportFormat;
+use crate::ignore::{parse_duration, Ignore};
 use crate::interface::Interface;
-use std::io::Result;
-use std::sync::mpsc::Sender;
+use crate::netsplit::split_servers;
+use crate::scrollback::{Line, Style};
+use crate::search::{build_regex, read_context, search_logs, search_store, Hit};
+use chrono::Utc;
+use regex::Regex;
+use std::io::{prelude::*, BufReader, Result};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+
+/// Connects to a network, adding it to the interface, and starts the
+/// thread reading from it
+pub fn start_network(itf: &Arc<Interface>, mut conn: Connection) -> Result<()> {
+    let mut stream = conn.connect()?;
+    send_auth(&conn, &mut stream)?;
+    let network = conn.server.clone();
+    let connected = format!("Connected to {}", conn.address);
+    let session = itf.add_network(conn);
+    itf.print_to_network(&network, &connected);
+
+    let itf = itf.clone();
+    thread::spawn(move || {
+        let printable = match read_network(&itf, &network, stream) {
+            Ok(()) => format!("Disconnected from {}, see :connect", network),
+            Err(e) => format!("Disconnected from {}: {}, see :connect", network, e),
+        };
+        if !itf.should_shutdown() && itf.close_network(&network, session) {
+            itf.print_to_network(&network, &printable);
+        }
+    });
+    Ok(())
+}
 
-pub fn parse_incoming_cmd(cmd: Command<'_>, itf: &Interface, pipe: &Sender<String>) -> Result<()> {
+/// Handles what network sends until it closes the connection
+fn read_network(itf: &Interface, network: &str, mut stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    loop {
+        let mut message = String::new();
+        if reader.read_line(&mut message)? == 0 {
+            return Ok(());
+        }
+        let command = Command::from(message.as_str());
+        let meta = Meta::from(message.as_str());
+        if let Some(reply) = command.reply() {
+            reply.send(&mut stream)?;
+        }
+        for line in answer_registration(&command, itf, network) {
+            stream.write_all(line.as_bytes())?;
+        }
+        parse_incoming_cmd(command, &meta, itf, network)?;
+
+        if itf.should_shutdown() {
+            return Ok(());
+        }
+    }
+}
+
+pub fn parse_incoming_cmd(
+    cmd: Command<'_>,
+    meta: &Meta<'_>,
+    itf: &Interface,
+    network: &str,
+) -> Result<()> {
+    // Ignored events aren't shown or logged, but still tell who is where
+    let ignored = meta
+        .hostmask
+        .is_some_and(|hostmask| itf.is_ignored(network, &cmd, hostmask));
     match cmd {
-        Command::Privmsg(sender, target, _) => {
-            let printable = cmd.to_printable().unwrap();
+        Command::Privmsg(..) | Command::Notice(..) if ignored => (),
 
-            let log_target = match target {
-                t if t == itf.get_username() => sender,
-                _ => target,
+        Command::Privmsg(sender, target, text) => {
+            let mut event = Event::new(&cmd, meta).unwrap();
+            let is_channel = itf.is_channel(network, target);
+            // Queries are named after the other party, which is the
+            // target of what we sent from elsewhere
+            let buffer = if is_channel || itf.is_own_nick(network, sender) {
+                target
+            } else {
+                sender
             };
+            highlight(&mut event, itf, network, buffer);
+            if is_channel {
+                itf.touch_nick(network, target, sender);
+            }
 
-            if let Some(pos) = itf.get_channel_pos(&log_target) {
-                itf.write_to_chan(pos, &printable)?;
-                if itf.is_active(&log_target) {
-                    pipe.send(printable).expect("Could not send to stdout");
+            if !itf.write_to_chan(network, buffer, &event) {
+                if is_channel {
+                    itf.write_to_network(network, &event);
+                } else {
+                    itf.push_channel(itf.open_channel(network, buffer));
+                    itf.write_to_chan(network, buffer, &event);
+                    itf.set_refresh_buffers_flag();
                 }
+            }
+            let private = !is_channel && buffer == sender;
+            if event.highlight || private {
+                itf.notify(network, buffer, sender, text);
+            }
+        }
+
+        Command::Notice(sender, target, text) => {
+            let mut event = Event::new(&cmd, meta).unwrap();
+            let buffer = if itf.is_channel(network, target) {
+                target
             } else {
-                let server = itf.get_server();
-                let mut c = Channel::new(log_target, &server);
-                c.write(&printable)?;
-                itf.push_channel(c);
-                itf.toggle_refresh_buffers_flag();
+                sender
+            };
+            highlight(&mut event, itf, network, buffer);
+            if !itf.write_to_chan(network, buffer, &event) {
+                itf.write_to_network(network, &event);
+            }
+            if event.highlight {
+                itf.notify(network, buffer, sender, text);
             }
         }
 
-        _ => {
-            if let Some(printable) = cmd.to_printable() {
-                itf.write_to_chan(0, &printable)?;
-                if itf.get_active_channel_pos() == 0 {
-                    pipe.send(printable).expect("Could not send to stdout");
+        // Buffers are opened and closed once the server confirms we
+        // joined or left, which it may also make us do
+        Command::UserJoin(nick, channel) => {
+            if itf.is_own_nick(network, nick) {
+                if itf.get_channel_pos(network, channel).is_none() {
+                    itf.push_channel(itf.open_channel(network, channel));
                 }
+                if itf.take_pending_join(network, channel) {
+                    itf.show_channel(network, channel);
+                    save_autojoin(itf, network, channel, true);
+                }
+                itf.set_refresh_buffers_flag();
+            }
+            if !ignored {
+                let mut event = Event::new(&cmd, meta).unwrap();
+                let rejoined = itf.add_netsplit_join(network, channel, nick);
+                write_member_event(itf, network, channel, nick, &mut event, rejoined);
+            }
+            itf.add_nick(network, channel, nick);
+        }
+
+        Command::UserPart(nick, channel, _) => {
+            if !ignored {
+                let mut event = Event::new(&cmd, meta).unwrap();
+                write_member_event(itf, network, channel, nick, &mut event, false);
+            }
+            if itf.is_own_nick(network, nick) {
+                close_channel(itf, network, channel);
+                save_autojoin(itf, network, channel, false);
+            } else {
+                itf.remove_nick(network, channel, nick);
+            }
+        }
+
+        // Kicks are never noise
+        Command::UserKick(kicker, channel, nick, reason) => {
+            if !ignored {
+                itf.write_to_chan(network, channel, &Event::new(&cmd, meta).unwrap());
+            }
+            if itf.is_own_nick(network, nick) {
+                let printable = format!("-- {} kicked you from {} ({})", kicker, channel, reason);
+                itf.print_to_network(network, &printable);
+                close_channel(itf, network, channel);
+            } else {
+                itf.remove_nick(network, channel, nick);
+            }
+        }
+
+        Command::JoinFailed(channel, _) => {
+            itf.take_pending_join(network, channel);
+            itf.write_to_network(network, &Event::new(&cmd, meta).unwrap());
+        }
+
+        Command::Forward(channel, to) => {
+            if itf.take_pending_join(network, channel) {
+                itf.add_pending_join(network, to);
+            }
+            itf.write_to_network(network, &Event::new(&cmd, meta).unwrap());
+        }
+
+        // Quits of a netsplit are summed up once it calms down
+        Command::UserQuit(nick, reason) => {
+            if !ignored {
+                let split = split_servers(reason);
+                let mut event = Event::new(&cmd, meta).unwrap();
+                for channel in itf.get_nick_channels(network, nick) {
+                    write_member_event(itf, network, &channel, nick, &mut event, split.is_some());
+                }
+                event.hidden = false;
+                itf.write_to_chan(network, nick, &event);
+                if let Some(servers) = split {
+                    itf.add_netsplit_quit(network, servers, nick);
+                }
+            }
+            itf.quit_nick(network, nick);
+        }
+
+        Command::UserNick(old, new) => {
+            if !ignored {
+                let mut event = Event::new(&cmd, meta).unwrap();
+                for channel in itf.get_nick_channels(network, old) {
+                    write_member_event(itf, network, &channel, old, &mut event, false);
+                }
+            }
+            if itf.is_own_nick(network, old) {
+                itf.set_nick(network, new);
+                itf.print_to_network(network, &format!("-- You are now known as {}", new));
+            } else {
+                // The query follows the other party, unless one with their
+                // new nick is open already
+                let query = if itf.rename_channel(network, old, new) {
+                    new
+                } else {
+                    old
+                };
+                if !ignored {
+                    itf.write_to_chan(network, query, &Event::new(&cmd, meta).unwrap());
+                }
+            }
+            itf.rename_nick(network, old, new);
+        }
+
+        Command::Topic(_, channel, _) => {
+            itf.write_to_chan(network, channel, &Event::new(&cmd, meta).unwrap());
+        }
+
+        Command::Names(channel, nicks) => {
+            for nick in nicks.split_whitespace() {
+                itf.add_nick(network, channel, nick.trim_start_matches(NICK_PREFIXES));
+            }
+        }
+
+        Command::Welcome(nick) => itf.set_nick(network, nick),
+
+        // Registration, SASL included, is over by the end of the MOTD
+        Command::MotdEnd => {
+            if let Some((lines, delay)) = itf.take_autojoin(network) {
+                for line in lines {
+                    itf.queue_line_after(network, line, delay);
+                }
+            }
+        }
+
+        Command::ISupport(params) => {
+            for (key, value) in params.split_whitespace().filter_map(|p| p.split_once('=')) {
+                match key {
+                    "CASEMAPPING" => {
+                        if let Ok(casemapping) = value.parse() {
+                            itf.set_casemapping(network, casemapping);
+                        }
+                    }
+                    "CHANTYPES" => itf.set_chantypes(network, value),
+                    "TARGMAX" => {
+                        let join = value.split(',').find_map(|t| t.strip_prefix("JOIN:"));
+                        itf.set_join_targets(network, join.and_then(|n| n.parse().ok()));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        _ => {
+            if let Some(event) = Event::new(&cmd, meta) {
+                itf.write_to_network(network, &event);
             }
         }
     }
     Ok(())
 }
 
+/// Marks an event sent to buffer as highlighting us if it does, copying
+/// it to the mentions buffer. What we sent never does.
+fn highlight(event: &mut Event<'_>, itf: &Interface, network: &str, buffer: &str) {
+    let from_us = event
+        .nick
+        .is_some_and(|nick| itf.is_own_nick(network, nick));
+    if !from_us && itf.is_highlight(network, buffer, event.text) {
+        event.highlight = true;
+        itf.add_mention(network, buffer, event);
+    }
+}
+
+/// Logs what nick did in channel of network, hiding it if it is summed
+/// up elsewhere or is noise. What we do is always shown.
+fn write_member_event(
+    itf: &Interface,
+    network: &str,
+    channel: &str,
+    nick: &str,
+    event: &mut Event<'_>,
+    summed_up: bool,
+) {
+    let own = itf.is_own_nick(network, nick);
+    event.hidden = !own && (summed_up || itf.is_noise(network, channel, nick));
+    itf.write_to_chan(network, channel, event);
+}
+
+/// Closes the buffer of a channel we left
+fn close_channel(itf: &Interface, network: &str, channel: &str) {
+    itf.remove_channel(network, channel);
+    itf.set_refresh_buffers_flag();
+}
+
+/// Updates the autojoin of network after we joined or left channel,
+/// reporting if it can't be saved
+fn save_autojoin(itf: &Interface, network: &str, channel: &str, joined: bool) {
+    if let Err(e) = itf.update_autojoin(network, &[channel], joined) {
+        let printable = format!("Could not save autojoin: {}", e);
+        itf.print_to_network(network, &printable);
+    }
+}
+
+/// Returns the lines to send the server to carry on registering after a
+/// command: SASL credentials, another nick if ours is taken, and once
+/// registered the commands on connect. Channels are autojoined at the end
+/// of the MOTD.
+pub fn answer_registration(cmd: &Command<'_>, itf: &Interface, network: &str) -> Vec<String> {
+    match cmd {
+        Command::Authenticate("+") => match itf.get_sasl(network) {
+            Some((username, password)) => sasl_plain(&username, &password),
+            None => vec![Command::Authenticate("*").to_unwrapped_string()],
+        },
+        Command::NickInUse("*") => {
+            vec![Command::Nick(&itf.next_nick(network)).to_unwrapped_string()]
+        }
+        Command::Welcome(_) => itf
+            .get_on_connect(network)
+            .iter()
+            .map(|line| format!("{}\r\n", line))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parses a user command, to be sent to the active buffer's network
 pub fn parse_user_cmd<'inp>(
     inp: &'inp str,
-    itf: &'_ Interface,
-    pipe: &'_ Sender<String>,
+    itf: &'_ Arc<Interface>,
     argv: &'inp [&'inp str],
 ) -> Command<'inp> {
-    match &inp[1..2] {
-        "q" => {
+    let name = inp[1..].split_whitespace().next().unwrap_or_default();
+    let network = itf.get_active_network();
+    match resolve_command(name).unwrap_or_default() {
+        "quit" => {
             let quitmsg = if argv.is_empty() {
                 "Quitting ..."
             } else {
-                &inp[2..]
+                inp[1..].trim_start()[name.len()..].trim()
             };
+            for other in itf.get_networks().iter().filter(|n| **n != network) {
+                itf.queue_line(other, Command::Quit(quitmsg).to_unwrapped_string());
+            }
             itf.set_shutdown_flag();
             Command::Quit(quitmsg)
         }
 
-        "j" => {
-            // TODO: check whether join is successful
-            for channel in argv {
-                itf.push_channel(Channel::new(channel, &itf.get_server()));
+        // Buffers are opened once the server confirms the join, and
+        // switched to as asked here
+        "join" => {
+            match argv.first() {
+                Some(channels) => {
+                    let channels: Vec<_> = channels.split(',').filter(|c| !c.is_empty()).collect();
+                    let keys: Vec<_> = argv.get(1).map_or(Vec::new(), |k| k.split(',').collect());
+                    for channel in &channels {
+                        itf.add_pending_join(&network, channel);
+                    }
+                    if let Err(e) = itf.send_line(&network, &join_line(&channels, &keys)) {
+                        itf.print_to_active(&format!("{}, see :connect", e));
+                    }
+                }
+                None => itf.print_to_active("Usage: :join <#channel[,#channel]> [key[,key]]"),
+            }
+            Command::Unknown
+        }
+
+        "part" => Command::Part(argv),
+
+        "query" => {
+            match argv.first() {
+                Some(nick) if !itf.is_channel(&network, nick) => {
+                    if itf.get_channel_pos(&network, nick).is_none() {
+                        itf.push_channel(itf.open_channel(&network, nick));
+                    }
+                    itf.show_channel(&network, nick);
+
+                    let msg = inp[1..].trim_start()[name.len()..].trim_start()[nick.len()..].trim();
+                    if !msg.is_empty() {
+                        let own_nick = itf.get_nick(&network);
+                        let privmsg = Command::Privmsg(&own_nick, nick, msg);
+                        let meta = Meta::now();
+                        let event = Event::new(&privmsg, &meta).unwrap();
+                        if let Err(e) = itf.send_to_chan(&network, nick, &privmsg, &event) {
+                            itf.print_to_chan(&network, nick, &e.to_string());
+                        }
+                    }
+                }
+                _ => {
+                    let printable = "Usage: :query <nick> [message]";
+                    itf.print_to_active(printable);
+                }
             }
-            itf.store_active_channel(itf.channels_len() - 1);
-            itf.toggle_refresh_buffers_flag();
-            Command::Join(&argv)
+            Command::Unknown
         }
 
-        "p" => {
-            for channel in argv {
-                if let Some(index) = itf.get_channel_pos(*channel) {
-                    itf.remove_channel(index);
-                    if itf.is_active(*channel) {
-                        itf.store_active_channel(itf.channels_len() - 1);
+        "ignore" => {
+            let args = inp[1..].trim_start()[name.len()..].trim();
+            if args.is_empty() {
+                let ignores = itf.get_ignores();
+                if ignores.is_empty() {
+                    itf.print_to_active("No ignores");
+                }
+                for (i, ignore) in ignores.iter().enumerate() {
+                    itf.print_to_active(&format!("[{}] {}", i, ignore));
+                }
+            } else {
+                let (flags, mask) = split_flags(args);
+                match parse_ignore(&flags, mask) {
+                    Ok(ignore) => {
+                        itf.print_to_active(&format!("Ignoring {}", ignore.describe()));
+                        itf.add_ignore(ignore);
                     }
-                    itf.toggle_refresh_buffers_flag();
+                    Err(e) => itf.print_to_active(&e),
                 }
             }
-            Command::Part(&argv)
+            Command::Unknown
+        }
+
+        "unignore" => {
+            let printable = match argv.first().map(|mask| itf.remove_ignore(mask)) {
+                Some(Some(ignore)) => format!("No longer ignoring {}", ignore.mask),
+                Some(None) => format!("No ignore of {}, see :ignore", argv[0]),
+                None => String::from("Usage: :unignore <mask>|<n>"),
+            };
+            itf.print_to_active(&printable);
+            Command::Unknown
         }
 
-        "c" => {
-            if let Ok(target) = &inp[2..].trim().parse::<usize>() {
-                if itf.get_channel(*target).is_some() {
-                    itf.store_active_channel(*target);
-                    itf.toggle_refresh_buffers_flag();
+        "channel" => {
+            let target = match argv.first() {
+                Some(arg) => arg
+                    .parse::<usize>()
+                    .ok()
+                    .or_else(|| itf.get_channel_pos(&network, arg)),
+                None => None,
+            };
+            if let Some(target) = target {
+                if itf.get_channel(target).is_some() {
+                    itf.store_active_channel(target);
+                    itf.set_refresh_buffers_flag();
                 }
             } else {
                 let mut printable = String::from("Buffers: ");
@@ -92,11 +496,259 @@ pub fn parse_user_cmd<'inp>(
                     let name = itf.get_channel(i).unwrap();
                     printable.push_str(&format!("[{}]{} ", i, name));
                 }
-                pipe.send(printable).expect("Could not send to stdout");
+                itf.print_to_active(&printable);
+            }
+            Command::Unknown
+        }
+
+        "search" => {
+            let args = inp[1..].trim_start()[name.len()..].trim();
+            let (flags, pattern) = split_flags(args);
+            match build_regex(pattern, flags.contains(&"-r")) {
+                _ if pattern.is_empty() => itf.clear_search(),
+                Ok(regex) if flags.contains(&"-a") => search_all_logs(itf, pattern, regex),
+                Ok(regex) => {
+                    let count = itf.search_active_channel(regex);
+                    let printable = match count {
+                        0 => format!("No matches for {}", pattern),
+                        _ => format!(
+                            "{} matches for {} (n: older, N: newer, Esc: end)",
+                            count, pattern
+                        ),
+                    };
+                    itf.print_to_active(&printable);
+                }
+                Err(e) => itf.print_to_active(&format!("Invalid search: {}", e)),
+            }
+            Command::Unknown
+        }
+
+        "context" => {
+            let hit = argv.first().and_then(|n| n.parse().ok());
+            match hit.and_then(|n| itf.get_search_hit(n)) {
+                Some(hit) => match read_context(&hit, CONTEXT_LINES, itf.get_store().as_deref()) {
+                    Ok((lines, pos)) => {
+                        let header = format!("--- {} ---", hit.location());
+                        let mut shown = vec![Line::new(&header, Style::Info)];
+                        for (i, line) in lines.iter().enumerate() {
+                            let (time, text) = parse_log_line(line);
+                            let style = if i == pos { Style::Normal } else { Style::Dim };
+                            shown.push(Line::with_time(time, text, style));
+                        }
+                        itf.show_scratch(CONTEXT_BUFFER, shown);
+                    }
+                    Err(e) => {
+                        let printable = format!("Could not read {}: {}", hit.label, e);
+                        itf.print_to_active(&printable);
+                    }
+                },
+                None => {
+                    let printable = "No such search result, see :search -a";
+                    itf.print_to_active(printable);
+                }
+            }
+            Command::Unknown
+        }
+
+        "export" => {
+            let format = argv.first().map(|f| f.parse::<ExportFormat>());
+            let dates = argv.iter().skip(1).map(|d| parse_date(d));
+            let printable = match (format, dates.collect::<Result<Vec<_>>>()) {
+                (Some(Ok(format)), Ok(ref dates)) if dates.len() <= 2 => {
+                    let from = dates.first().copied();
+                    match itf.export_active_channel(format, from, dates.get(1).copied()) {
+                        Ok(fp) => format!("Exported to {}", fp.display()),
+                        Err(e) => format!("Could not export: {}", e),
+                    }
+                }
+                (Some(Err(e)), _) => e,
+                (_, Err(e)) => e.to_string(),
+                _ => String::from("Usage: :export html|markdown [FROM [TO]]"),
+            };
+            itf.print_to_active(&printable);
+            Command::Unknown
+        }
+
+        "reload" => {
+            let printable = match itf.reload_config() {
+                Ok((settings, diffs)) => describe_reload(&settings, &diffs),
+                Err(e) => format!("Could not reload the configuration: {}", e),
+            };
+            itf.print_to_active(&printable);
+            Command::Unknown
+        }
+
+        "connect" => {
+            let printable = match argv.first().map(|target| itf.new_connection(target)) {
+                Some(Ok(conn)) => {
+                    let server = conn.server.clone();
+                    match start_network(itf, conn) {
+                        Ok(()) => {
+                            itf.show_channel(&server, &server);
+                            None
+                        }
+                        Err(e) => Some(format!("Could not connect to {}: {}", server, e)),
+                    }
+                }
+                Some(Err(e)) => Some(e),
+                None => Some(String::from("Usage: :connect <network|host[:port]>")),
+            };
+            if let Some(printable) = printable {
+                itf.print_to_active(&printable);
             }
             Command::Unknown
         }
 
-        &_ => Command::Unknown,
+        "disconnect" => {
+            let target = argv.first().map_or(network, |n| n.to_string());
+            let networks = itf.get_networks();
+            if !networks.contains(&target) {
+                let printable = format!("No network named {}", target);
+                itf.print_to_active(&printable);
+            } else if networks.len() == 1 {
+                let printable = "Can't disconnect from the last network, see :quit";
+                itf.print_to_active(printable);
+            } else {
+                let _ = itf.send(&target, &Command::Quit("Disconnecting ..."));
+                itf.remove_network(&target);
+            }
+            Command::Unknown
+        }
+
+        _ => Command::Unknown,
+    }
+}
+
+/// Returns what a reload of the configuration did to the settings and on
+/// each network, and what it couldn't
+fn describe_reload(settings: &ConfigDiff, diffs: &[(String, ConfigDiff)]) -> String {
+    let mut printable = String::from("Configuration reloaded");
+    let mut applied = settings.applied.clone();
+    let mut reconnect = settings.reconnect.clone();
+    let kept = &settings.kept;
+    let (mut join, mut part) = (Vec::new(), Vec::new());
+    for (network, diff) in diffs {
+        applied.extend(diff.applied.iter().cloned());
+        reconnect.extend(diff.reconnect.iter().cloned());
+        join.extend(diff.join.iter().map(|c| format!("{} on {}", c, network)));
+        part.extend(diff.part.iter().map(|c| format!("{} on {}", c, network)));
+    }
+    let parts = [
+        ("applied", &applied),
+        ("joining", &join),
+        ("leaving", &part),
+        ("reconnect to apply", &reconnect),
+        ("kept as given on the command line", kept),
+    ];
+    let changes: Vec<_> = parts
+        .iter()
+        .filter(|(_, keys)| !keys.is_empty())
+        .map(|(what, keys)| format!("{}: {}", what, keys.join(", ")))
+        .collect();
+    if changes.is_empty() {
+        printable.push_str(", nothing changed");
+    } else {
+        printable.push_str("; ");
+        printable.push_str(&changes.join("; "));
+    }
+    printable.push_str(&format!(
+        ". Not configurable yet: {}",
+        UNCONFIGURABLE.join(", ")
+    ));
+    printable
+}
+
+/// Splits leading flags like -a off a command's arguments, returning them
+/// and the rest. "--" ends the flags.
+fn split_flags(args: &str) -> (Vec<&str>, &str) {
+    let mut flags = Vec::new();
+    let mut rest = args;
+    while rest.starts_with('-') {
+        let (flag, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        rest = tail.trim_start();
+        if flag == "--" {
+            break;
+        }
+        flags.push(flag);
+    }
+    (flags, rest)
+}
+
+/// Returns the ignore of mask that flags of :ignore describe
+fn parse_ignore(flags: &[&str], mask: &str) -> std::result::Result<Ignore, String> {
+    if mask.is_empty() || mask.contains(char::is_whitespace) {
+        return Err(String::from(IGNORE_USAGE));
+    }
+    let mut ignore = Ignore::new(mask)?;
+    for flag in flags {
+        match flag.split_once('=') {
+            Some(("-t", types)) => {
+                ignore.types = types
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<std::result::Result<_, _>>()?;
+            }
+            Some(("-c", channels)) => {
+                ignore.channels = channels.split(',').map(str::to_owned).collect();
+            }
+            Some(("-e", duration)) => ignore.expires = Some(Utc::now() + parse_duration(duration)?),
+            _ => return Err(String::from(IGNORE_USAGE)),
+        }
+    }
+    Ok(ignore)
+}
+
+/// Searches all logs of the active network in the background, showing the
+/// results in their own buffer. The SQLite store is searched instead if
+/// there is one.
+fn search_all_logs(itf: &Arc<Interface>, pattern: &str, regex: Regex) {
+    let network = itf.get_active_network();
+    let store = itf.get_store();
+    let dir = itf.get_logs_dir();
+    let json = itf.logs_json_only();
+    let printable = format!("Searching the logs for {} ...", pattern);
+    itf.print_to_active(&printable);
+
+    let itf = itf.clone();
+    let pattern = pattern.to_owned();
+    thread::spawn(move || {
+        let hits = match (store, dir) {
+            (Some(store), _) => search_store(&store, &network, &regex),
+            (None, Some(dir)) => Ok(search_logs(&dir, &regex, json)),
+            (None, None) => Ok(Vec::new()),
+        };
+        match hits {
+            Ok(hits) => show_hits(&itf, &pattern, &regex, hits),
+            Err(e) => {
+                let printable = format!("Could not search the logs: {}", e);
+                itf.print_to_active(&printable);
+            }
+        }
+    });
+}
+
+/// Shows the hits of a search of the logs in their own buffer
+fn show_hits(itf: &Interface, pattern: &str, regex: &Regex, hits: Vec<Hit>) {
+    let header = format!(
+        "--- {} matches for {} in the logs, see :context <n> ---",
+        hits.len(),
+        pattern
+    );
+    let mut lines = vec![Line::new(&header, Style::Info)];
+    for (i, hit) in hits.iter().enumerate() {
+        let (time, text) = parse_log_line(&hit.text);
+        let text = format!("[{}] {}: {}", i, hit.label, text);
+        lines.push(Line::with_time(time, &text, Style::Normal));
+    }
+    itf.store_search_hits(hits);
+    itf.show_scratch(SEARCH_BUFFER, lines);
+    itf.search_active_channel(regex.clone());
+}
+
+/// Returns the full name of the user command name is an abbreviation of
+pub fn resolve_command(name: &str) -> Option<&'static str> {
+    if name.is_empty() {
+        return None;
     }
+    COMMANDS.iter().copied().find(|cmd| cmd.starts_with(name))
 }
@@ -1,5 +1,23 @@
+const WHEEL_LINES: usize = 3;
+const CTRL_A: char = '\u{1}';
+const CTRL_G: char = '\u{7}';
+const CTRL_R: char = '\u{12}';
+const ESCAPE: char = '\u{1b}';
+
+/// Asks the terminal to report gaining and losing focus, as ESC [ I and
+/// ESC [ O, and to stop again
+const FOCUS_REPORTS_ON: &str = "\x1b[?1004h";
+const FOCUS_REPORTS_OFF: &str = "\x1b[?1004l";
+
+use crate::channel::Activity;
+use crate::completion::Completer;
+use crate::history::History;
 use crate::interface::Interface;
+use crate::scrollback::{Scroll, Style};
+use crate::thread_tools::COMMAND_PREFIX;
 use pancurses::*;
+use regex::Regex;
+use std::io::{stdout, Write};
 use std::sync::mpsc::Sender;
 
 #[macro_export]
@@ -22,55 +40,128 @@ pub fn init_curses(debug: bool) -> Window {
     term.clear();
     term.refresh();
     term.keypad(true);
+    mousemask(BUTTON4_PRESSED | BUTTON5_PRESSED, std::ptr::null_mut());
 
     if has_colors() {
         use_default_colors();
         start_color();
         define_colour_pairs();
     }
+    write_terminal(FOCUS_REPORTS_ON);
 
     term
 }
 
+/// Restores the terminal
+pub fn end_curses() {
+    write_terminal(FOCUS_REPORTS_OFF);
+    endwin();
+}
+
+/// Writes an escape sequence to the terminal, around curses
+fn write_terminal(escape: &str) {
+    let mut out = stdout();
+    // Terminals that can't do it are no worse off
+    let _ = out.write_all(escape.as_bytes()).and_then(|()| out.flush());
+}
+
+/// Writes the notifications made since the last call to the terminal,
+/// after curses refreshed the screen so they don't garble it
+pub fn write_notifications(interface: &Interface) {
+    for escape in interface.take_notifications() {
+        write_terminal(&escape);
+    }
+}
+
 fn define_colour_pairs() {
     init_pair(0, -1, -1); // defaults, no colours
     init_pair(1, COLOR_RED, -1); // red on black
     init_pair(2, COLOR_BLUE, -1); // blue on black
     init_pair(3, COLOR_GREEN, -1); // green on black
+    init_pair(4, COLOR_YELLOW, -1); // yellow on black
 }
 
-pub fn split_line(s: &str, max_len: usize) -> Vec<String> {
-    let mut lines = Vec::new();
-    let words = s.split_whitespace();
-    let mut line = String::with_capacity(max_len);
+/// Returns the attributes and colour pair a line style is drawn with
+fn style_attrs(style: Style) -> (chtype, i16) {
+    match style {
+        Style::Normal => (A_NORMAL, 0),
+        Style::Info => (A_NORMAL, 3),
+        Style::Dim => (A_DIM, 0),
+        Style::Highlight => (A_BOLD, 4),
+    }
+}
 
-    for word in words {
-        if word.len() + line.len() < max_len {
-            line.push_str(word);
-            line.push(' ');
-        } else {
-            line.insert(line.len() - 1, '\n');
-            lines.push(line);
-            line = String::with_capacity(max_len);
-            line.push_str(word);
-            line.push(' ');
-        }
+/// Creates the buffers, output and input windows, filling the terminal
+pub fn create_windows(term: &Window) -> (Window, Window, Window) {
+    let (term_rows, term_cols) = term.get_max_yx();
+    let buffers_win = term.subwin(1, term_cols, 0, 0).unwrap();
+    let output_win = term.subwin(term_rows - 2, term_cols, 1, 0).unwrap();
+    let input_win = term.subwin(1, term_cols, term_rows - 1, 0).unwrap();
+    (buffers_win, output_win, input_win)
+}
+
+pub fn handle_input(
+    mut inp: String,
+    w: &Window,
+    term: &Window,
+    pipe: &Sender<String>,
+    interface: &Interface,
+    history: &mut History,
+    completer: &mut Completer,
+) -> String {
+    let mut key = term.getch();
+    // Focus reports are noted whatever the input is doing
+    if key == Some(Input::Character(ESCAPE)) {
+        key = read_escape(term, interface);
     }
 
-    if !line.is_empty() {
-        line.insert(line.len() - 1, '\n');
-        lines.push(line);
+    if key.is_some() && key != Some(Input::Character('\t')) {
+        completer.reset();
     }
 
-    lines
-}
+    if history.is_searching() {
+        return handle_history_search(inp, key, w, pipe, interface, history);
+    }
+
+    // While the output is looked through, / starts another search
+    let browsing = interface.is_search_nav() || interface.is_active_scrolled();
+    if key == Some(Input::Character('/')) && inp.is_empty() && browsing {
+        interface.end_search_nav();
+        let prompt = format!("{}search ", COMMAND_PREFIX);
+        redraw_input(w, &prompt);
+        return prompt;
+    }
+
+    if interface.is_search_nav() && inp.is_empty() && handle_search_nav(key, w, interface) {
+        return inp;
+    }
 
-pub fn handle_input(mut inp: String, w: &Window, term: &Window, pipe: &Sender<String>) -> String {
     let (y, x) = w.get_cur_yx();
-    match term.getch() {
+    match key {
         Some(Input::KeyResize) => {
             resize_term(0, 0);
         }
+        Some(Input::KeyPPage) => {
+            interface.scroll_active_channel(Scroll::Up(page_size(term)));
+        }
+        Some(Input::KeyNPage) => {
+            interface.scroll_active_channel(Scroll::Down(page_size(term)));
+        }
+        Some(Input::KeyHome) => {
+            interface.scroll_active_channel(Scroll::Top);
+        }
+        Some(Input::KeyEnd) => {
+            interface.scroll_active_channel(Scroll::Bottom);
+        }
+        Some(Input::KeyMouse) => {
+            if let Ok(event) = getmouse() {
+                if event.bstate & BUTTON4_PRESSED != 0 {
+                    interface.scroll_active_channel(Scroll::Up(WHEEL_LINES));
+                } else if event.bstate & BUTTON5_PRESSED != 0 {
+                    interface.scroll_active_channel(Scroll::Down(WHEEL_LINES));
+                }
+            }
+        }
         Some(Input::KeyBackspace) => {
             w.mv(y, x - 1);
             inp.pop();
@@ -82,9 +173,10 @@ pub fn handle_input(mut inp: String, w: &Window, term: &Window, pipe: &Sender<St
             w.chgat(1, A_REVERSE, 0);
         }
         Some(Input::KeyUp) => {
-            w.chgat(1, A_NORMAL, 0);
-            w.mv(y, 0);
-            w.chgat(1, A_REVERSE, 0);
+            if let Some(line) = history.prev(&interface.get_active_channel(), &inp) {
+                inp = line.to_owned();
+                redraw_input(w, &inp);
+            }
         }
         Some(Input::KeyRight) => {
             if x < inp.len() as i32 {
@@ -94,15 +186,33 @@ pub fn handle_input(mut inp: String, w: &Window, term: &Window, pipe: &Sender<St
             }
         }
         Some(Input::KeyDown) => {
-            w.chgat(1, A_NORMAL, 0);
-            w.mv(y, inp.len() as i32);
-            w.chgat(1, A_REVERSE, 0);
+            if let Some(line) = history.next(&interface.get_active_channel()) {
+                inp = line.to_owned();
+                redraw_input(w, &inp);
+            }
+        }
+        Some(Input::Character('\n')) => {
+            inp = submit_input(inp, w, pipe, interface, history);
+        }
+        Some(Input::Character('\t')) => {
+            let active = interface.get_active_channel();
+            let nicks = interface.get_nicks(&interface.get_active_network(), &active);
+            let channels = interface.get_channels();
+            if let Some(line) = completer.complete(&inp, &nicks, &channels) {
+                inp = line;
+                redraw_input(w, &inp);
+            }
+        }
+        Some(Input::Character(ESCAPE)) => (),
+        Some(Input::Character(CTRL_A)) => {
+            if let Some(pos) = interface.next_active_channel() {
+                interface.store_active_channel(pos);
+                interface.set_refresh_buffers_flag();
+            }
         }
-        Some(Input::Character(c)) if c == '\n' => {
-            pipe.send(inp).expect("Could not send to WRITE");
-            w.deleteln();
-            w.mv(1, 0);
-            inp = String::default();
+        Some(Input::Character(CTRL_R)) => {
+            history.search_start(&interface.get_active_channel(), &inp);
+            redraw_input(w, &history.search_prompt().unwrap_or_default());
         }
         Some(Input::Character(c)) => {
             inp.push(c);
@@ -116,29 +226,203 @@ pub fn handle_input(mut inp: String, w: &Window, term: &Window, pipe: &Sender<St
     inp
 }
 
-pub fn shift_lines_up(w: &Window, last_line: i32) {
-    if w.get_cur_y() >= last_line {
-        w.mv(0, 0);
-        w.deleteln();
-        w.mv(last_line, 0);
+/// Reads what follows an escape. Focus reports are noted and used up,
+/// returning no key. Otherwise the escape is returned, and what followed it
+/// is read again as typed.
+fn read_escape(term: &Window, interface: &Interface) -> Option<Input> {
+    match term.getch() {
+        Some(Input::Character('[')) => match term.getch() {
+            Some(Input::Character('I')) => {
+                interface.set_focused(true);
+                return None;
+            }
+            Some(Input::Character('O')) => {
+                interface.set_focused(false);
+                return None;
+            }
+            Some(key) => {
+                term.ungetch(&key);
+            }
+            None => (),
+        },
+        Some(key) => {
+            term.ungetch(&key);
+        }
+        None => (),
+    }
+    Some(Input::Character(ESCAPE))
+}
+
+/// Handles a key press while an incremental reverse history search is
+/// running. inp is the line that was being edited when the search started.
+fn handle_history_search(
+    inp: String,
+    key: Option<Input>,
+    w: &Window,
+    pipe: &Sender<String>,
+    interface: &Interface,
+    history: &mut History,
+) -> String {
+    let buffer = interface.get_active_channel();
+    match key {
+        Some(Input::Character(CTRL_R)) => history.search_start(&buffer, &inp),
+        Some(Input::KeyBackspace) => history.search_pop(&buffer),
+        Some(Input::Character('\n')) => {
+            let line = history.search_end(true);
+            return submit_input(line, w, pipe, interface, history);
+        }
+        Some(Input::Character(ESCAPE)) | Some(Input::Character(CTRL_G)) => {
+            let line = history.search_end(false);
+            redraw_input(w, &line);
+            return line;
+        }
+        Some(Input::Character(c)) if !c.is_control() => history.search_push(&buffer, c),
+        Some(Input::KeyResize) | None => return inp,
+        Some(_) => {
+            // Any other key accepts the match for editing
+            let line = history.search_end(true);
+            redraw_input(w, &line);
+            return line;
+        }
+    }
+    redraw_input(w, &history.search_prompt().unwrap_or_default());
+    inp
+}
+
+/// Handles a key press while n/N jump between search matches, showing
+/// which match is shown in the input window. Returns whether the key was
+/// used up; any key other than n, N, Escape or scrolling ends jumping.
+fn handle_search_nav(key: Option<Input>, w: &Window, interface: &Interface) -> bool {
+    let jumped = match key {
+        Some(Input::Character('n')) => interface.next_match(true),
+        Some(Input::Character('N')) => interface.next_match(false),
+        Some(Input::Character(ESCAPE)) => {
+            interface.clear_search();
+            redraw_input(w, "");
+            return true;
+        }
+        Some(Input::KeyPPage)
+        | Some(Input::KeyNPage)
+        | Some(Input::KeyHome)
+        | Some(Input::KeyEnd)
+        | Some(Input::KeyMouse)
+        | Some(Input::KeyResize)
+        | None => {
+            return false;
+        }
+        Some(_) => {
+            interface.end_search_nav();
+            redraw_input(w, "");
+            return false;
+        }
+    };
+    if let Some((n, count)) = jumped {
+        redraw_input(
+            w,
+            &format!("match {}/{} (n: older, N: newer, Esc: end)", n, count),
+        );
     }
+    true
+}
+
+/// Sends the input line to the write thread, records it in the history
+/// and clears the input window. Returns the new (empty) input line.
+fn submit_input(
+    inp: String,
+    w: &Window,
+    pipe: &Sender<String>,
+    interface: &Interface,
+    history: &mut History,
+) -> String {
+    // Failing to persist the history shouldn't keep the line from being sent
+    let _ = history.push(&interface.get_active_channel(), &inp);
+    pipe.send(inp).expect("Could not send to WRITE");
+    w.deleteln();
+    w.mv(1, 0);
+    String::default()
+}
+
+/// Returns the number of lines PageUp/PageDown scroll the output by
+fn page_size(term: &Window) -> usize {
+    // Leave one line of overlap, minus the buffers and input windows
+    (term.get_max_y() - 3).max(1) as usize
+}
+
+/// Replaces the contents of the input window with inp, placing the cursor
+/// at its end
+pub fn redraw_input(w: &Window, inp: &str) {
+    w.mv(0, 0);
+    w.clrtoeol();
+    w.addstr(inp);
+    w.chgat(1, A_REVERSE, 0);
 }
 
 pub fn refresh_buffers(w: &Window, interface: &Interface) {
-    if interface.should_refresh_buffers() || w.is_touched() {
+    if interface.take_refresh_buffers_flag() || w.is_touched() {
         w.mv(0, 0);
         w.deleteln();
         for i in 0..interface.channels_len() {
             let name = interface.get_channel(i).unwrap();
-            if i == interface.get_active_channel_pos() {
-                w.attron(A_BOLD);
-                w.color_set(1);
+            // Each network's buffers follow its own
+            if i > 0 && interface.is_root(i) {
+                w.addstr("| ");
             }
-            w.addstr(&format!("[{}]{} ", i, name));
-            w.attroff(A_BOLD);
-            w.color_set(0);
+            let (activity, unread) = interface.get_activity(i);
+            let (attrs, colour) = match activity {
+                _ if i == interface.get_active_channel_pos() => (A_BOLD, 1),
+                Activity::Highlight => (A_BOLD, 4),
+                Activity::Messages => (A_BOLD, 0),
+                Activity::Events => (A_NORMAL, 2),
+                Activity::None => (A_NORMAL, 0),
+            };
+            w.attrset(attrs | COLOR_PAIR(colour));
+            match unread {
+                0 => w.addstr(format!("[{}]{} ", i, name)),
+                n => w.addstr(format!("[{}]{}({}) ", i, name, n)),
+            };
+            w.attrset(A_NORMAL);
         }
         w.refresh();
-        interface.toggle_refresh_buffers_flag();
     }
 }
+
+pub fn refresh_output(w: &Window, interface: &Interface) {
+    if interface.take_refresh_output_flag() || w.is_touched() {
+        let (rows, cols) = w.get_max_yx();
+        let view = interface.get_active_view(cols as usize, rows as usize);
+        let search = interface.get_active_search();
+
+        w.erase();
+        for (y, (row, style)) in view.rows.iter().enumerate() {
+            let (attrs, colour) = style_attrs(*style);
+            w.mv(y as i32, 0);
+            w.attrset(attrs | COLOR_PAIR(colour as chtype));
+            match search {
+                Some(ref regex) if *style != Style::Info => draw_matches(w, row, regex),
+                _ => {
+                    w.addstr(row);
+                }
+            }
+        }
+        if view.more > 0 {
+            w.mv(rows - 1, 0);
+            w.attrset(A_REVERSE);
+            w.addstr(format!("-- more ({}) --", view.more));
+        }
+        w.attrset(A_NORMAL);
+        w.refresh();
+    }
+}
+
+/// Draws row with the parts matching regex highlighted
+fn draw_matches(w: &Window, row: &str, regex: &Regex) {
+    let mut drawn = 0;
+    for m in regex.find_iter(row) {
+        w.addstr(&row[drawn..m.start()]);
+        w.attron(A_REVERSE);
+        w.addstr(m.as_str());
+        w.attroff(A_REVERSE);
+        drawn = m.end();
+    }
+    w.addstr(&row[drawn..]);
+}
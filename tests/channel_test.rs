@@ -0,0 +1,205 @@
+use libminirc::channel::*;
+use libminirc::connection::CaseMapping;
+use libminirc::logfile::LogConfig;
+use std::env;
+use std::fs;
+
+#[test]
+pub fn reading_log_tail_works() {
+    let fp = env::temp_dir().join("minirc_read_tail_test.txt");
+    let lines: Vec<_> = (0..2000).map(|i| format!("<Ranmaru> line {}", i)).collect();
+    fs::write(&fp, lines.join("\n") + "\n").unwrap();
+
+    let tail = read_tail(&fp, 3).unwrap();
+    assert_eq!(
+        tail,
+        vec![
+            "<Ranmaru> line 1997",
+            "<Ranmaru> line 1998",
+            "<Ranmaru> line 1999"
+        ]
+    );
+    assert_eq!(read_tail(&fp, 5000).unwrap().len(), 2000);
+    assert!(read_tail(&fp, 0).unwrap().is_empty());
+    fs::remove_file(&fp).unwrap();
+}
+
+#[test]
+pub fn log_line_format_works() {
+    use chrono::{TimeZone, Utc};
+    use libminirc::scrollback::{Line, Style};
+
+    let time = Utc.with_ymd_and_hms(2020, 6, 1, 12, 30, 5).unwrap();
+    let line = Line::with_time(Some(time), "<Ranmaru> Hi there", Style::Normal);
+    let logged = format_log_line(&line);
+    assert_eq!(logged, "2020-06-01T12:30:05Z <Ranmaru> Hi there");
+    assert_eq!(parse_log_line(&logged), (Some(time), "<Ranmaru> Hi there"));
+    assert_eq!(
+        parse_log_line("<Ranmaru> old line"),
+        (None, "<Ranmaru> old line")
+    );
+}
+
+#[test]
+pub fn json_logging_works() {
+    use libminirc::command::{Command, Meta};
+
+    let dir = env::temp_dir().join("minirc_json_log_test");
+    let raw = "@msgid=abc :Ranmaru!~ranmaru@host PRIVMSG ##foo :Hello World!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+    let event = Event::new(&cmd, &meta).unwrap();
+
+    let log = LogConfig {
+        format: LogFormat::Both,
+        dir: Some(dir.clone()),
+        ..LogConfig::default()
+    };
+    let mut chan = Channel::new("##Foo", "freenode", &log, CaseMapping::Rfc1459);
+    assert!(chan.is("##foo"));
+    chan.write(&event);
+
+    let logs = dir.join("freenode");
+    let text = fs::read_to_string(logs.join("##foo.txt")).unwrap();
+    assert!(text.ends_with(" <Ranmaru> Hello World!\n"));
+    let json: JsonEvent =
+        serde_json::from_str(&read_tail(&logs.join("##foo.jsonl"), 1).unwrap()[0]).unwrap();
+    assert_eq!(json.network, "freenode");
+    assert_eq!(json.buffer, "##Foo");
+    assert_eq!(json.kind, "privmsg");
+    assert_eq!(json.nick.as_deref(), Some("Ranmaru"));
+    assert_eq!(json.hostmask.as_deref(), Some("Ranmaru!~ranmaru@host"));
+    assert_eq!(json.text, "Hello World!");
+    assert_eq!(json.msgid.as_deref(), Some("abc"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn tracking_activity_works() {
+    use libminirc::command::{Command, Meta};
+
+    let raw = ":Ranmaru!~ranmaru@host PRIVMSG ##foo :Hello World!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+    let mut event = Event::new(&cmd, &meta).unwrap();
+    let raw = ":Ranmaru!~ranmaru@host NICK :Ran\r\n";
+    let (nick, nick_meta) = (Command::from(raw), Meta::from(raw));
+
+    let mut chan = Channel::scratch("##foo", "freenode");
+    assert_eq!(chan.get_activity(), (Activity::None, 0));
+    chan.add_activity(Activity::of(&Event::new(&nick, &nick_meta).unwrap()));
+    assert_eq!(chan.get_activity(), (Activity::Events, 0));
+    chan.add_activity(Activity::of(&event));
+    event.highlight = true;
+    chan.add_activity(Activity::of(&event));
+    event.highlight = false;
+    chan.add_activity(Activity::of(&event));
+    assert_eq!(chan.get_activity(), (Activity::Highlight, 3));
+    chan.clear_activity();
+    assert_eq!(chan.get_activity(), (Activity::None, 0));
+}
+
+#[test]
+pub fn remembering_speakers_works() {
+    use chrono::{Duration, Utc};
+    use libminirc::command::{Command, Meta};
+
+    let before = Utc::now() - Duration::minutes(1);
+    let raw = ":Ranmaru!~ranmaru@host PRIVMSG ##foo :Hello World!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+
+    let mut chan = Channel::scratch("##foo", "freenode");
+    assert!(!chan.has_spoken_since("Ranmaru", before));
+    chan.write(&Event::new(&cmd, &meta).unwrap());
+    assert!(chan.has_spoken_since("ranmaru", before));
+    assert!(!chan.has_spoken_since("Ranmaru", Utc::now() + Duration::minutes(1)));
+    chan.rename_nick("Ranmaru", "Ran");
+    assert!(!chan.has_spoken_since("Ranmaru", before));
+    assert!(chan.has_spoken_since("Ran", before));
+}
+
+#[test]
+pub fn log_failures_are_reported_once() {
+    use chrono::{Duration, Utc};
+    use libminirc::command::{Command, Meta};
+
+    let dir = env::temp_dir().join("minirc_log_failure_test");
+    let raw = ":Ranmaru!~ranmaru@host PRIVMSG ##foo :Hello World!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+    let event = Event::new(&cmd, &meta).unwrap();
+    let log = LogConfig {
+        dir: Some(dir.clone()),
+        ..LogConfig::default()
+    };
+    let mut chan = Channel::new("##foo", "freenode", &log, CaseMapping::Rfc1459);
+    let fp = dir.join("freenode").join("##foo.txt");
+
+    fs::remove_file(&fp).unwrap();
+    chan.write(&event);
+    chan.write(&event);
+    assert!(chan.has_spoken_since("Ranmaru", Utc::now() - Duration::minutes(1)));
+    assert_eq!(chan.take_log_errors().len(), 1);
+
+    fs::write(&fp, "").unwrap();
+    chan.write(&event);
+    assert!(chan.take_log_errors().is_empty());
+    fs::remove_file(&fp).unwrap();
+    chan.write(&event);
+    assert_eq!(chan.take_log_errors().len(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn store_failures_are_reported_once() {
+    use libminirc::command::{Command, Meta};
+    use libminirc::store::Store;
+    use std::sync::Arc;
+
+    let dir = env::temp_dir().join("minirc_store_failure_test");
+    let _ = fs::remove_dir_all(&dir);
+    let raw = ":Ranmaru!~ranmaru@host PRIVMSG ##foo :Hello World!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+    let event = Event::new(&cmd, &meta).unwrap();
+    let log = LogConfig {
+        sqlite: true,
+        dir: Some(dir.clone()),
+        ..LogConfig::default()
+    };
+    let mut chan = Channel::new("##foo", "freenode", &log, CaseMapping::Rfc1459);
+    let fp = dir.join("events.db");
+    chan.set_store(Arc::new(Store::open(&fp).unwrap()));
+    rusqlite::Connection::open(&fp)
+        .unwrap()
+        .execute_batch("DROP TABLE events")
+        .unwrap();
+
+    chan.write(&event);
+    chan.write(&event);
+    assert_eq!(chan.take_log_errors().len(), 1);
+    let text = fs::read_to_string(dir.join("freenode").join("##foo.txt")).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn changing_casemapping_moves_logs() {
+    use libminirc::command::{Command, Meta};
+
+    let dir = env::temp_dir().join("minirc_casemapping_log_test");
+    let _ = fs::remove_dir_all(&dir);
+    let raw = ":Ranmaru!~ranmaru@host PRIVMSG #Foo[bar] :Hello World!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+    let event = Event::new(&cmd, &meta).unwrap();
+    let log = LogConfig {
+        dir: Some(dir.clone()),
+        ..LogConfig::default()
+    };
+    let mut chan = Channel::new("#Foo[bar]", "freenode", &log, CaseMapping::Rfc1459);
+    chan.write(&event);
+    chan.set_casemapping(CaseMapping::Ascii).unwrap();
+    chan.write(&event);
+
+    let logs = dir.join("freenode");
+    assert!(!logs.join("#foo{bar}.txt").exists());
+    let text = fs::read_to_string(logs.join("#foo[bar].txt")).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -5,7 +5,7 @@ pub fn parsing_privmsg_works() {
     let test_str = ":Ranmaru!~ranmaru@2a02:908:13b2:5380:6c18:852b:8306:ac33 PRIVMSG ##rantestfoobazinga1337 :Foo! :D";
     let expected = Command::Privmsg("Ranmaru", "##rantestfoobazinga1337", "Foo! :D");
     let as_sent = String::from("PRIVMSG ##rantestfoobazinga1337 :Foo! :D\r\n");
-    assert_eq!(Command::from_str(test_str), expected);
+    assert_eq!(Command::from(test_str), expected);
     assert_eq!(expected.to_string(), Some(as_sent));
 }
 
@@ -14,7 +14,7 @@ pub fn parsing_notice_works() {
     let test_str = ":niven.freenode.net NOTICE * :*** Looking up your hostname...";
     let expected = Command::Notice("niven.freenode.net", "*", "*** Looking up your hostname...");
     let as_sent = String::from("NOTICE * :*** Looking up your hostname...\r\n");
-    assert_eq!(Command::from_str(test_str), expected);
+    assert_eq!(Command::from(test_str), expected);
     assert_eq!(expected.to_string(), Some(as_sent));
 }
 
@@ -23,7 +23,7 @@ pub fn parsing_ping_works() {
     let test_str = ":niven.freenode.net PING :pong me back";
     let expected = Command::Ping(":pong me back");
     let as_sent = String::from("PING :pong me back\r\n");
-    assert_eq!(Command::from_str(test_str), expected);
+    assert_eq!(Command::from(test_str), expected);
     assert_eq!(expected.to_string(), Some(as_sent));
 }
 
@@ -32,7 +32,7 @@ pub fn parsing_pong_works() {
     let test_str = ":Ranmaru!~ranmaru@2a02:908:13b2:5380:6c18:852b:8306:ac33 PONG :pong pong pong";
     let expected = Command::Pong(":pong pong pong");
     let as_sent = String::from("PONG :pong pong pong\r\n");
-    assert_eq!(Command::from_str(test_str), expected);
+    assert_eq!(Command::from(test_str), expected);
     assert_eq!(expected.to_string(), Some(as_sent));
 }
 
@@ -53,4 +53,162 @@ pub fn printing_works() {
         privmsg.to_printable().unwrap(),
         String::from("<Ranmaru> Hello World!")
     );
+    let action = Command::Privmsg("Ranmaru", "##foo", "\u{1}ACTION waves\u{1}");
+    assert_eq!(action.to_printable().unwrap(), "* Ranmaru waves");
+}
+
+#[test]
+pub fn parsing_membership_works() {
+    let join = ":Ranmaru!~ranmaru@host JOIN :##foo\r\n";
+    let part = ":Ranmaru!~ranmaru@host PART ##foo :Bye bye\r\n";
+    let names = ":niven.freenode.net 353 minirc_user = ##foo :@Ranmaru +alice bob\r\n";
+    let nick = ":Ranmaru!~ranmaru@host NICK :Ran\r\n";
+    assert_eq!(Command::from(join), Command::UserJoin("Ranmaru", "##foo"));
+    assert_eq!(
+        Command::from(part),
+        Command::UserPart("Ranmaru", "##foo", "Bye bye")
+    );
+    assert_eq!(
+        Command::from(names),
+        Command::Names("##foo", "@Ranmaru +alice bob")
+    );
+    assert_eq!(
+        Command::from(join).to_printable().unwrap(),
+        "--> Ranmaru has joined"
+    );
+    assert_eq!(
+        Command::from(part).to_printable().unwrap(),
+        "<-- Ranmaru has left (Bye bye)"
+    );
+    assert_eq!(Command::from(nick), Command::UserNick("Ranmaru", "Ran"));
+    assert_eq!(
+        Command::from(nick).to_printable().unwrap(),
+        "-- Ranmaru is now known as Ran"
+    );
+    let quit = ":Ranmaru!~ranmaru@host QUIT :Ping timeout\r\n";
+    assert_eq!(
+        Command::from(quit).to_printable().unwrap(),
+        "<-- Ranmaru has quit (Ping timeout)"
+    );
+    let kick = ":alice!~alice@host KICK ##foo Ranmaru :Behave\r\n";
+    assert_eq!(
+        Command::from(kick).to_printable().unwrap(),
+        "<-- Ranmaru was kicked by alice (Behave)"
+    );
+
+    let banned =
+        ":niven.freenode.net 474 Ranmaru ##foo :Cannot join channel (+b) - you are banned\r\n";
+    let forward =
+        ":niven.freenode.net 470 Ranmaru ##foo ##foo-overflow :Forwarding to another channel\r\n";
+    assert_eq!(
+        Command::from(banned),
+        Command::JoinFailed("##foo", "Cannot join channel (+b) - you are banned")
+    );
+    assert_eq!(
+        Command::from(forward),
+        Command::Forward("##foo", "##foo-overflow")
+    );
+
+    let topic = ":Ranmaru!~ranmaru@host TOPIC ##foo :Rust: now with more 'static\r\n";
+    assert_eq!(
+        Command::from(topic),
+        Command::Topic("Ranmaru", "##foo", "Rust: now with more 'static")
+    );
+}
+
+#[test]
+pub fn parsing_tags_works() {
+    let test_str =
+        "@time=2020-06-01T12:30:05.123Z;msgid=abc :Ranmaru!~ranmaru@host PRIVMSG ##foo :Hi";
+    let meta = Meta::from(test_str);
+    assert_eq!(
+        Command::from(test_str),
+        Command::Privmsg("Ranmaru", "##foo", "Hi")
+    );
+    assert_eq!(meta.time.to_rfc3339(), "2020-06-01T12:30:05.123+00:00");
+    assert_eq!(
+        meta.tags,
+        vec![("time", "2020-06-01T12:30:05.123Z"), ("msgid", "abc")]
+    );
+}
+
+#[test]
+pub fn replying_works() {
+    let ping = Command::from("PING :niven.freenode.net\r\n");
+    let ack = Command::from(":niven.freenode.net CAP * ACK :server-time\r\n");
+    assert_eq!(ping, Command::Ping(":niven.freenode.net"));
+    assert_eq!(ping.reply(), Some(Command::Pong(":niven.freenode.net")));
+    assert_eq!(ack, Command::Cap("ACK", "server-time"));
+    assert_eq!(ack.reply().unwrap().to_unwrapped_string(), "CAP END\r\n");
+}
+
+#[test]
+pub fn registering_works() {
+    let ack = Command::from(":niven.freenode.net CAP * ACK :server-time sasl\r\n");
+    assert_eq!(ack.reply(), Some(Command::Authenticate("PLAIN")));
+    assert_eq!(
+        Command::from("AUTHENTICATE +\r\n"),
+        Command::Authenticate("+")
+    );
+    assert_eq!(
+        sasl_plain("ranmaru", "hunter2"),
+        vec!["AUTHENTICATE cmFubWFydQByYW5tYXJ1AGh1bnRlcjI=\r\n"]
+    );
+    let done = Command::from(":niven.freenode.net 903 Ranmaru :SASL authentication successful\r\n");
+    assert_eq!(done, Command::SaslDone(true));
+    assert_eq!(done.reply(), Some(Command::Cap("END", "")));
+
+    let taken = ":niven.freenode.net 433 * Ranmaru :Nickname is already in use\r\n";
+    let welcome = ":niven.freenode.net 001 Ranmaru_ :Welcome to freenode\r\n";
+    assert_eq!(Command::from(taken), Command::NickInUse("*"));
+    assert_eq!(Command::from(welcome), Command::Welcome("Ranmaru_"));
+}
+
+#[test]
+pub fn parsing_isupport_works() {
+    use libminirc::connection::CaseMapping;
+
+    let isupport = ":niven.freenode.net 005 minirc_user CHANTYPES=# CASEMAPPING=rfc1459 :are supported by this server\r\n";
+    assert_eq!(
+        Command::from(isupport),
+        Command::ISupport("CHANTYPES=# CASEMAPPING=rfc1459")
+    );
+
+    assert_eq!("rfc1459".parse(), Ok(CaseMapping::Rfc1459));
+    assert_eq!(CaseMapping::Rfc1459.fold("#Rust[~]"), "#rust{^}");
+    assert_eq!(CaseMapping::StrictRfc1459.fold("#Rust[~]"), "#rust{~}");
+    assert_eq!(CaseMapping::Ascii.fold("#Rust[~]"), "#rust[~]");
+    assert!(CaseMapping::Rfc1459.is_same("#Rust", "#rust"));
+}
+
+#[test]
+pub fn joining_with_keys_works() {
+    assert_eq!(split_key("#rust"), ("#rust", None));
+    assert_eq!(split_key("#secret:hunter2"), ("#secret", Some("hunter2")));
+
+    let entries: Vec<String> = ["#a", "#b:key", "#c", "#d:pass"]
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        join_lines(&entries, None),
+        vec!["JOIN #b,#d,#a,#c key,pass\r\n"]
+    );
+    assert_eq!(
+        join_lines(&entries, Some(3)),
+        vec!["JOIN #b,#d,#a key,pass\r\n", "JOIN #c\r\n"]
+    );
+    assert_eq!(
+        join_line(&["#secret", "#a"], &["key"]),
+        "JOIN #secret,#a key\r\n"
+    );
+
+    let many: Vec<String> = (0..100).map(|n| format!("#channel{}", n)).collect();
+    let lines = join_lines(&many, None);
+    assert!(lines.len() > 1);
+    assert!(lines.iter().all(|line| line.len() <= 512));
+    assert_eq!(
+        Command::from(":niven.freenode.net 376 Ranmaru :End of /MOTD command.\r\n"),
+        Command::MotdEnd
+    );
 }
@@ -0,0 +1,50 @@
+use libminirc::completion::*;
+
+fn owned(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+pub fn completing_nicks_works() {
+    let nicks = owned(&["Ranmaru", "rando", "alice"]);
+    let mut completer = Completer::new(':');
+    assert_eq!(
+        completer.complete("ra", &nicks, &[]),
+        Some(String::from("Ranmaru: "))
+    );
+    assert_eq!(
+        completer.complete("Ranmaru: ", &nicks, &[]),
+        Some(String::from("rando: "))
+    );
+    assert_eq!(
+        completer.complete("rando: ", &nicks, &[]),
+        Some(String::from("Ranmaru: "))
+    );
+    completer.reset();
+    assert_eq!(
+        completer.complete("hi al", &nicks, &[]),
+        Some(String::from("hi alice "))
+    );
+}
+
+#[test]
+pub fn completing_channels_and_commands_works() {
+    let channels = owned(&["freenode", "##rust", "#minirc"]);
+    let mut completer = Completer::new(':');
+    assert_eq!(
+        completer.complete("see #m", &[], &channels),
+        Some(String::from("see #minirc "))
+    );
+    completer.reset();
+    assert_eq!(
+        completer.complete(":jo", &[], &channels),
+        Some(String::from(":join "))
+    );
+    completer.reset();
+    assert_eq!(
+        completer.complete(":p ##", &[], &channels),
+        Some(String::from(":p ##rust "))
+    );
+    completer.reset();
+    assert_eq!(completer.complete(":x", &[], &channels), None);
+}
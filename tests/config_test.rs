@@ -0,0 +1,195 @@
+use libminirc::config::*;
+use libminirc::history::HistoryMode;
+use libminirc::notify::NotifyMethod;
+use libminirc::settings::Settings;
+use std::time::Duration;
+use std::{env, fs};
+
+const CONFIG: &str = r###"
+default_network = "libera"
+history = "global"
+backlog = 50
+smart_filter = 30
+
+[log]
+format = "both"
+retention = 30
+
+[highlight]
+keywords = ["minirc"]
+exclude = ["#offtopic"]
+
+[[ignore]]
+mask = "*!*@spam.example"
+types = ["messages", "ctcp"]
+
+[[ignore]]
+mask = "troll"
+channels = ["#rust"]
+expires = "2100-01-01"
+
+[notify]
+command = "notify-send \"$MINIRC_BUFFER\" \"$MINIRC_TEXT\""
+quiet_hours = "23:00-07:00"
+
+[networks.libera]
+addresses = ["irc.libera.chat", "irc.eu.libera.chat:6665"]
+nick = "Ranmaru"
+alt_nicks = ["Ranmaru_", "Ran"]
+realname = "Mori Ranmaru"
+sasl = { username = "ranmaru", password = "hunter2" }
+autojoin = ["#rust", "##foo"]
+on_connect = ["MODE Ranmaru +i"]
+
+[networks.local]
+addresses = ["127.0.0.1"]
+port = 16667
+"###;
+
+#[test]
+pub fn loading_config_works() {
+    let config = Config::parse(CONFIG).unwrap();
+    let mut settings = Settings::default();
+    config.apply(&mut settings).unwrap();
+    assert_eq!(settings.history_mode, HistoryMode::Global);
+    assert_eq!(settings.backlog_lines, 50);
+    assert_eq!(settings.smart_filter, Some(Duration::from_secs(30 * 60)));
+    assert_eq!(settings.log.retention_days, 30);
+    assert!(settings.log.format.has_json());
+    assert_eq!(settings.highlights.keywords, vec!["minirc"]);
+    assert!(settings.highlights.nick);
+    assert!(matches!(settings.notifier.method, NotifyMethod::Command(_)));
+    assert!(settings.notifier.quiet_hours.is_some());
+    assert_eq!(settings.ignores.len(), 2);
+    assert_eq!(
+        settings.ignores[1].describe(),
+        "troll in #rust until 2100-01-01 00:00 UTC"
+    );
+
+    let (name, network) = config.network(None).unwrap().unwrap();
+    let mut conn = network.connection(name, "minirc_user").unwrap();
+    assert_eq!(conn.server, "libera");
+    assert_eq!(
+        conn.addresses,
+        vec!["irc.libera.chat:6667", "irc.eu.libera.chat:6665"]
+    );
+    assert_eq!(
+        (conn.nick.as_str(), conn.username.as_str()),
+        ("Ranmaru", "Ranmaru")
+    );
+    assert_eq!(conn.realname, "Mori Ranmaru");
+    assert_eq!(
+        conn.sasl,
+        Some((String::from("ranmaru"), String::from("hunter2")))
+    );
+    assert_eq!(conn.next_nick(), "Ranmaru_");
+    assert_eq!(conn.next_nick(), "Ran");
+    assert_eq!(conn.next_nick(), "Ran_");
+
+    let (name, network) = config.network(Some("local")).unwrap().unwrap();
+    let conn = network.connection(name, "minirc_user").unwrap();
+    assert_eq!(conn.address, "127.0.0.1:16667");
+    assert_eq!(conn.nick, "minirc_user");
+    assert!(config.network(Some("oftc")).is_err());
+    assert!(Config::parse("").unwrap().network(None).unwrap().is_none());
+
+    let conn = config.connection_to("local", "Ran").unwrap();
+    assert_eq!(
+        (conn.address.as_str(), conn.nick.as_str()),
+        ("127.0.0.1:16667", "Ran")
+    );
+    let conn = config.connection_to("irc.oftc.net:6697", "Ran").unwrap();
+    assert_eq!(
+        (conn.server.as_str(), conn.address.as_str()),
+        ("irc.oftc.net", "irc.oftc.net:6697")
+    );
+    let conn = config.connection_to("irc.oftc.net", "Ran").unwrap();
+    assert_eq!(conn.address, "irc.oftc.net:6667");
+    let conn = config.connection_to("[2001:db8::1]:6697", "Ran").unwrap();
+    assert_eq!(
+        (conn.server.as_str(), conn.address.as_str()),
+        ("2001:db8::1", "[2001:db8::1]:6697")
+    );
+    let conn = config.connection_to("2001:db8::1", "Ran").unwrap();
+    assert_eq!(conn.address, "[2001:db8::1]:6667");
+
+    let config = Config::parse(
+        "[networks.v6]\naddresses = [\"::1\", \"[::1]:6697\", \"[::2]\"]\nport = 7000",
+    )
+    .unwrap();
+    let conn = config.connection_to("v6", "Ran").unwrap();
+    assert_eq!(
+        conn.addresses,
+        vec!["[::1]:7000", "[::1]:6697", "[::2]:7000"]
+    );
+}
+
+#[test]
+pub fn invalid_config_is_reported() {
+    assert!(Config::parse("backlog = \"many\"").is_err());
+    assert!(Config::parse("[networks.x]\naddress = \"irc.example.org\"").is_err());
+
+    let mut settings = Settings::default();
+    let config = Config::parse("history = \"sometimes\"").unwrap();
+    let err = config.apply(&mut settings).unwrap_err();
+    assert!(err.starts_with("history: "));
+
+    let config = Config::parse("[highlight]\nregexes = [\"(\"]").unwrap();
+    let err = config.apply(&mut settings).unwrap_err();
+    assert!(err.starts_with("highlight.regexes: "));
+
+    let config = Config::parse("[[ignore]]\nmask = \"x\"\ntypes = [\"parts\"]").unwrap();
+    let err = config.apply(&mut settings).unwrap_err();
+    assert!(err.starts_with("ignore: "));
+    let config = Config::parse("[notify]\nmethod = \"command\"").unwrap();
+    let err = config.apply(&mut settings).unwrap_err();
+    assert!(err.starts_with("notify.command: "));
+    let config = Config::parse("[notify]\nquiet_hours = \"late\"").unwrap();
+    let err = config.apply(&mut settings).unwrap_err();
+    assert!(err.starts_with("notify.quiet_hours: "));
+
+    let config = Config::parse("[networks.x]\naddresses = [\"a\"]\ntls = true").unwrap();
+    let (name, network) = config.network(Some("x")).unwrap().unwrap();
+    let err = network.connection(name, "minirc_user").unwrap_err();
+    assert!(err.starts_with("networks.x.tls: "));
+}
+
+#[test]
+pub fn diffing_config_works() {
+    let old = Config::parse(CONFIG).unwrap();
+    let toml = CONFIG
+        .replace("backlog = 50", "backlog = 10")
+        .replace("nick = \"Ranmaru\"", "nick = \"Ran\"")
+        .replace("[\"#rust\", \"##foo\"]", "[\"#rust\", \"#bar\"]");
+    let new = Config::parse(&toml).unwrap();
+
+    let mut diff = new.diff(&old);
+    assert_eq!(diff.applied, vec!["backlog"]);
+    assert!(diff.join.is_empty() && diff.reconnect.is_empty());
+    diff.keep(&["backlog", "timestamp"]);
+    assert!(diff.applied.is_empty());
+    assert_eq!(diff.kept, vec!["backlog"]);
+
+    let diff = new.diff_network(&old, "libera");
+    assert!(diff.applied.is_empty());
+    assert_eq!(diff.join, vec!["#bar"]);
+    assert_eq!(diff.part, vec!["##foo"]);
+    assert_eq!(diff.reconnect, vec!["networks.libera.nick"]);
+    assert_eq!(new.diff_network(&old, "local"), ConfigDiff::default());
+}
+
+#[test]
+pub fn saving_autojoin_works() {
+    let fp = env::temp_dir().join("minirc_save_autojoin_test.toml");
+    fs::write(&fp, CONFIG).unwrap();
+    let autojoin = vec![String::from("#rust"), String::from("#secret:hunter2")];
+    save_autojoin(&fp, "libera", &autojoin).unwrap();
+
+    let saved = fs::read_to_string(&fp).unwrap();
+    assert!(saved.contains("autojoin = [\"#rust\", \"#secret:hunter2\"]"));
+    assert!(saved.contains("on_connect = [\"MODE Ranmaru +i\"]"));
+    let config = Config::load(&fp).unwrap();
+    assert_eq!(config.networks["libera"].autojoin, autojoin);
+    assert_eq!(config.networks["local"].autojoin, Vec::<String>::new());
+    fs::remove_file(&fp).unwrap();
+}
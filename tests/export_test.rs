@@ -0,0 +1,62 @@
+use chrono::NaiveDate;
+use libminirc::connection::CaseMapping;
+use libminirc::export::*;
+use libminirc::logfile::LogConfig;
+use std::env;
+use std::fs;
+
+#[test]
+pub fn stripping_formatting_works() {
+    let text = "\u{2}bold\u{2} \u{3}4,12red\u{3} \u{1d}it\u{f}\u{3}99 plain";
+    assert_eq!(strip_formatting(text), "bold red it plain");
+    assert_eq!(strip_formatting("\u{3}3,x"), ",x");
+}
+
+#[test]
+pub fn exporting_works() {
+    let dir = env::temp_dir().join("minirc_export_test");
+    fs::create_dir_all(dir.join("irc.example.org/#rust")).unwrap();
+    let day = |d| dir.join(format!("irc.example.org/#rust/2020-06-0{}.log", d));
+    let first = "2020-06-01T12:30:05Z <alice> see \u{3}4<b>\u{3} & _this_\n\
+                 2020-06-01T12:31:00Z * bob waves\n\
+                 2020-06-01T12:32:00Z --> carol has joined\n";
+    fs::write(day(1), first).unwrap();
+    fs::write(day(2), "2020-06-02T08:00:00Z <bob> \u{3}99,4later\n").unwrap();
+    fs::write(day(3), "2020-06-03T08:00:00Z <bob> too late\n").unwrap();
+
+    let log = LogConfig {
+        path: String::from("{network}/{channel}/{yyyy}-{mm}-{dd}.log"),
+        timezone: "UTC".parse().unwrap(),
+        ..LogConfig::default()
+    };
+    let mut options = ExportOptions {
+        format: ExportFormat::Html,
+        network: String::from("irc.example.org"),
+        buffer: String::from("#Rust"),
+        from: NaiveDate::from_ymd_opt(2020, 6, 1),
+        to: NaiveDate::from_ymd_opt(2020, 6, 2),
+        output: None,
+    };
+
+    let html = export(&options, &log, &dir, CaseMapping::default()).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<h2 id=\"2020-06-02\">2020-06-02</h2>"));
+    assert!(html.contains("id=\"L1\"><a class=\"time\" href=\"#L1\">12:30:05</a>"));
+    assert!(html.contains("<span class=\"f4\">&lt;b&gt;</span> &amp; _this_"));
+    assert!(html.contains("<span class=\"g4\">later</span>"));
+    assert!(!html.contains("too late"));
+
+    options.format = ExportFormat::Markdown;
+    let md = export(&options, &log, &dir, CaseMapping::default()).unwrap();
+    let lines: Vec<_> = md.lines().filter(|l| l.starts_with("- ")).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "- `12:30:05` **alice**: see \\<b\\> & \\_this\\_",
+            "- `12:31:00` \\* **bob** waves",
+            "- `12:32:00` _--\\> carol has joined_",
+            "- `08:00:00` **bob**: later",
+        ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,26 @@
+use libminirc::connection::CaseMapping;
+use libminirc::highlight::Highlights;
+
+#[test]
+pub fn highlighting_works() {
+    let keywords = vec![String::from("minirc")];
+    let patterns = vec![String::from(r"\bbugs?\b")];
+    let exclude = vec![String::from("#offtopic")];
+    let rules = Highlights::new(true, &keywords, &patterns, &exclude).unwrap();
+    let is_match =
+        |text: &str, buffer: &str| rules.is_match(text, "Ran[m]", buffer, CaseMapping::Rfc1459);
+
+    assert!(is_match("ran{m}: hi", "#rust"));
+    assert!(is_match("hi RAN[M]!", "#rust"));
+    assert!(!is_match("hi Ran[m]_", "#rust"));
+    assert!(!is_match("hi Ran", "#rust"));
+    assert!(is_match("MiniRC's search", "#rust"));
+    assert!(!is_match("miniRCs", "#rust"));
+    assert!(is_match("found a Bug", "#rust"));
+    assert!(!is_match("debugging", "#rust"));
+    assert!(!is_match("ran[m]: hi", "#OffTopic"));
+
+    let rules = Highlights::new(false, &[], &[], &[]).unwrap();
+    assert!(!rules.is_match("Ran: hi", "Ran", "#rust", CaseMapping::Ascii));
+    assert!(Highlights::new(true, &[], &[String::from("(")], &[]).is_err());
+}
@@ -0,0 +1,51 @@
+use libminirc::history::*;
+
+#[test]
+pub fn browsing_history_works() {
+    let mut history = History::new(HistoryMode::Global, None);
+    history.push("#foo", "first").unwrap();
+    history.push("#bar", "second").unwrap();
+    assert_eq!(history.prev("#foo", "draft"), Some("second"));
+    assert_eq!(history.prev("#foo", "second"), Some("first"));
+    assert_eq!(history.prev("#foo", "first"), None);
+    assert_eq!(history.next("#foo"), Some("second"));
+    assert_eq!(history.next("#foo"), Some("draft"));
+    assert_eq!(history.next("#foo"), None);
+}
+
+#[test]
+pub fn per_buffer_history_works() {
+    let mut history = History::new(HistoryMode::PerBuffer, None);
+    history.push("#foo", "first").unwrap();
+    history.push("#bar", "second").unwrap();
+    history.push("#foo", "third").unwrap();
+    assert_eq!(history.prev("#foo", ""), Some("third"));
+    assert_eq!(history.prev("#foo", ""), Some("first"));
+    history.reset();
+    assert_eq!(history.prev("#bar", ""), Some("second"));
+    assert_eq!(history.prev("#bar", ""), None);
+}
+
+#[test]
+pub fn reverse_search_works() {
+    let mut history = History::new(HistoryMode::Global, None);
+    history.push("#foo", "hello world").unwrap();
+    history.push("#foo", "goodbye").unwrap();
+    history.push("#foo", "hello again").unwrap();
+
+    history.search_start("#foo", "draft");
+    for c in "hel".chars() {
+        history.search_push("#foo", c);
+    }
+    assert_eq!(
+        history.search_prompt(),
+        Some(String::from("(reverse-i-search)`hel': hello again"))
+    );
+    history.search_start("#foo", "draft");
+    assert_eq!(history.search_end(true), "hello world");
+    assert!(!history.is_searching());
+
+    history.search_start("#foo", "draft");
+    history.search_push("#foo", 'x');
+    assert_eq!(history.search_end(true), "draft");
+}
@@ -0,0 +1,50 @@
+use chrono::{Duration, TimeZone, Utc};
+use libminirc::command::Command;
+use libminirc::connection::CaseMapping;
+use libminirc::ignore::*;
+
+#[test]
+pub fn ignoring_works() {
+    let casemapping = CaseMapping::Rfc1459;
+    let msg = Command::from(":Troll!~troll@spam.example PRIVMSG #rust :hi\r\n");
+    let ctcp = Command::from(":Troll!~troll@spam.example PRIVMSG Ran :\u{1}VERSION\u{1}\r\n");
+    let quit = Command::from(":Troll!~troll@spam.example QUIT :bye\r\n");
+    let hostmask = "Troll!~troll@spam.example";
+
+    let ignore = Ignore::new("troll").unwrap();
+    assert!(ignore.is_match(&msg, hostmask, casemapping));
+    assert!(ignore.is_match(&quit, hostmask, casemapping));
+    assert!(!ignore.is_match(&msg, "Trolling!~troll@spam.example", casemapping));
+    assert!(Ignore::new("*!*@*.EXAMPLE")
+        .unwrap()
+        .is_match(&msg, hostmask, casemapping));
+    assert!(Ignore::new("/^troll!.*@spam\\./")
+        .unwrap()
+        .is_match(&msg, hostmask, casemapping));
+    assert!(!Ignore::new("t?oll!*@other.example")
+        .unwrap()
+        .is_match(&msg, hostmask, casemapping));
+    assert!(Ignore::new("/(/").is_err());
+
+    let mut ignore = Ignore::new("Troll").unwrap();
+    ignore.types = vec![IgnoreType::Ctcp];
+    assert!(ignore.is_match(&ctcp, hostmask, casemapping));
+    assert!(!ignore.is_match(&msg, hostmask, casemapping));
+    ignore.types = Vec::new();
+    ignore.channels = vec![String::from("#Rust")];
+    assert!(ignore.is_match(&msg, hostmask, casemapping));
+    assert!(!ignore.is_match(&quit, hostmask, casemapping));
+    assert_eq!(ignore.describe(), "Troll in #Rust");
+
+    let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+    ignore.expires = Some(now + parse_duration("2h").unwrap());
+    assert!(!ignore.is_expired(now));
+    assert!(ignore.is_expired(now + Duration::days(1)));
+    assert!(parse_duration("0h").is_err());
+    assert!(parse_duration("2w").is_err());
+    assert_eq!(
+        parse_expiry("2024-06-01").unwrap(),
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    );
+    assert_eq!(parse_expiry("2024-06-01T14:00:00+02:00").unwrap(), now);
+}
@@ -0,0 +1,147 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use libminirc::import::*;
+use libminirc::logfile::LogConfig;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn at(day: u32, h: u32, m: u32, s: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2020, 6, day)
+        .unwrap()
+        .and_hms_opt(h, m, s)
+        .unwrap()
+}
+
+fn summary(entries: &[LogEntry]) -> Vec<(NaiveDateTime, &str, Option<&str>, &str)> {
+    entries
+        .iter()
+        .map(|e| (e.time, e.kind, e.nick.as_deref(), e.text.as_str()))
+        .collect()
+}
+
+#[test]
+pub fn parsing_irssi_logs_works() {
+    let log = "--- Log opened Mon Jun 01 12:30:05 2020
+12:30 -!- Ranmaru [~ranmaru@host] has joined ##foo
+12:31 <@Ranmaru> Hello World!
+12:32  * bob waves
+12:33 -!- bob [~bob@host] has quit [Ping timeout]
+--- Day changed Tue Jun 02 2020
+00:01:02 <+alice> past midnight
+";
+    let entries = parse_irssi(log);
+    assert_eq!(
+        summary(&entries),
+        vec![
+            (at(1, 12, 30, 0), "join", Some("Ranmaru"), ""),
+            (at(1, 12, 31, 0), "privmsg", Some("Ranmaru"), "Hello World!"),
+            (
+                at(1, 12, 32, 0),
+                "privmsg",
+                Some("bob"),
+                "\u{1}ACTION waves\u{1}"
+            ),
+            (at(1, 12, 33, 0), "quit", Some("bob"), "Ping timeout"),
+            (at(2, 0, 1, 2), "privmsg", Some("alice"), "past midnight"),
+        ]
+    );
+    assert_eq!(
+        entries[0].hostmask.as_deref(),
+        Some("Ranmaru!~ranmaru@host")
+    );
+}
+
+#[test]
+pub fn parsing_weechat_logs_works() {
+    let log = "2020-06-01 12:30:05\t-->\tRanmaru (~ranmaru@host) has joined ##foo
+2020-06-01 12:31:00\t@Ranmaru\tHello World!
+2020-06-01 12:32:00\t *\tbob waves
+2020-06-01 12:33:00\t<--\tbob (~bob@host) has left ##foo (bye)
+2020-06-01 12:34:00\t--\tbob is now known as bobby
+";
+    assert_eq!(
+        summary(&parse_weechat(log)),
+        vec![
+            (at(1, 12, 30, 5), "join", Some("Ranmaru"), ""),
+            (at(1, 12, 31, 0), "privmsg", Some("Ranmaru"), "Hello World!"),
+            (
+                at(1, 12, 32, 0),
+                "privmsg",
+                Some("bob"),
+                "\u{1}ACTION waves\u{1}"
+            ),
+            (at(1, 12, 33, 0), "part", Some("bob"), "bye"),
+            (at(1, 12, 34, 0), "nick", Some("bob"), "bobby"),
+        ]
+    );
+}
+
+#[test]
+pub fn parsing_hexchat_logs_works() {
+    let log = "**** BEGIN LOGGING AT Mon Jun  1 12:30:05 2020
+
+Jun 01 12:30:05 *\tRanmaru (~ranmaru@host) has joined ##foo
+Jun 01 12:31:00 <Ranmaru>\tHello World!
+Jun 01 12:32:00 *\tbob waves
+Jun 01 12:33:00 -NickServ-\tYou are now identified
+";
+    assert_eq!(
+        summary(&parse_hexchat(log)),
+        vec![
+            (at(1, 12, 30, 5), "join", Some("Ranmaru"), ""),
+            (at(1, 12, 31, 0), "privmsg", Some("Ranmaru"), "Hello World!"),
+            (
+                at(1, 12, 32, 0),
+                "privmsg",
+                Some("bob"),
+                "\u{1}ACTION waves\u{1}"
+            ),
+            (
+                at(1, 12, 33, 0),
+                "notice",
+                Some("NickServ"),
+                "You are now identified"
+            ),
+        ]
+    );
+}
+
+#[test]
+pub fn guessing_buffers_works() {
+    let guess = |source, path| guess_buffer(source, Path::new(path));
+    assert_eq!(
+        guess(LogSource::Irssi, "irclogs/libera/##foo.log"),
+        Some((String::from("libera"), String::from("##foo")))
+    );
+    assert_eq!(
+        guess(LogSource::Weechat, "logs/irc.libera.##foo.weechatlog"),
+        Some((String::from("libera"), String::from("##foo")))
+    );
+    assert_eq!(
+        guess(LogSource::Weechat, "logs/core.weechat.weechatlog"),
+        None
+    );
+}
+
+#[test]
+pub fn importing_repeated_lines_works() {
+    let dir = env::temp_dir().join("minirc_import_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let fp = dir.join("irc.freenode.##foo.weechatlog");
+    let line = "2020-06-01 12:31:00\tbob\tlol\n";
+    fs::write(&fp, line.repeat(2)).unwrap();
+    let options = ImportOptions {
+        source: LogSource::Weechat,
+        network: Some(String::from("freenode")),
+        files: vec![fp.clone()],
+    };
+    let logs_dir = dir.join("logs");
+    let log = LogConfig::default();
+    assert_eq!(import_logs(&options, &log, &logs_dir).unwrap(), 2);
+    assert_eq!(import_logs(&options, &log, &logs_dir).unwrap(), 0);
+
+    fs::write(&fp, line.repeat(3)).unwrap();
+    assert_eq!(import_logs(&options, &log, &logs_dir).unwrap(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,143 @@
+use chrono::NaiveDate;
+use libminirc::logfile::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn dated_config() -> LogConfig {
+    LogConfig {
+        path: String::from("{network}/{channel}/{yyyy}-{mm}-{dd}.log"),
+        ..LogConfig::default()
+    }
+}
+
+#[test]
+pub fn log_path_template_works() {
+    let log = dated_config();
+    let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+    assert!(log.is_dated());
+    assert!(!LogConfig::default().is_dated());
+    assert_eq!(
+        log.render("freenode", "##foo", date),
+        Path::new("freenode/##foo/2020-06-01.log")
+    );
+
+    for path in &[
+        "freenode/##foo/2020-06-01.log",
+        "freenode/##foo/2020-06-01.log.gz",
+        "freenode/##foo/2020-06-01.jsonl",
+        "freenode/##foo/2020-06-01.jsonl.gz",
+    ] {
+        assert_eq!(
+            log.date_of("freenode", "##foo", Path::new(path)),
+            Some(date)
+        );
+    }
+    for path in &[
+        "freenode/##bar/2020-06-01.log",
+        "freenode/##foo/2020-6-01.log",
+        "freenode/##foo/2020-13-01.log",
+        "freenode/##foo/2020-06-01.txt",
+    ] {
+        assert_eq!(log.date_of("freenode", "##foo", Path::new(path)), None);
+    }
+
+    assert!(log.validate().is_ok());
+    let no_channel = LogConfig {
+        path: String::from("{network}.log"),
+        ..LogConfig::default()
+    };
+    assert!(no_channel.validate().is_err());
+}
+
+#[test]
+pub fn log_retention_works() {
+    let logs_dir = env::temp_dir().join("minirc_log_retention_test");
+    let log = LogConfig {
+        retention_days: 7,
+        timezone: "UTC".parse().unwrap(),
+        ..dated_config()
+    };
+    let today = log.timezone.today();
+    let old = today - chrono::Duration::days(30);
+    for date in &[today, old] {
+        let fp = logs_dir.join(log.render("freenode", "##foo", *date));
+        fs::create_dir_all(fp.parent().unwrap()).unwrap();
+        fs::write(&fp, "2020-06-01T12:30:05Z <Ranmaru> Hi there\n").unwrap();
+    }
+
+    let old_fp = logs_dir.join(log.render("freenode", "##foo", old));
+    compress(&old_fp).unwrap();
+    assert!(!old_fp.exists());
+    let gz_fp = old_fp.with_extension("log.gz");
+    assert_eq!(
+        read_gz_tail(&gz_fp, 5).unwrap(),
+        vec!["2020-06-01T12:30:05Z <Ranmaru> Hi there"]
+    );
+
+    assert_eq!(log.dated_logs(&logs_dir, "freenode", "##foo").len(), 2);
+    log.apply_retention(&logs_dir, "freenode", "##foo").unwrap();
+    let logs = log.dated_logs(&logs_dir, "freenode", "##foo");
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].0, today);
+    fs::remove_dir_all(&logs_dir).unwrap();
+}
+
+#[test]
+pub fn compressing_closed_logs_works() {
+    let logs_dir = env::temp_dir().join("minirc_log_compress_test");
+    let log = LogConfig {
+        compress: true,
+        timezone: "UTC".parse().unwrap(),
+        ..dated_config()
+    };
+    let today = log.timezone.today();
+    let yesterday = today - chrono::Duration::days(1);
+    for date in &[today, yesterday] {
+        let fp = logs_dir.join(log.render("freenode", "##foo", *date));
+        fs::create_dir_all(fp.parent().unwrap()).unwrap();
+        fs::write(&fp, "2020-06-01T12:30:05Z <Ranmaru> Hi there\n").unwrap();
+    }
+
+    log.compress_closed(&logs_dir, "freenode", "##foo", today)
+        .unwrap();
+    let names: Vec<_> = log
+        .dated_logs(&logs_dir, "freenode", "##foo")
+        .iter()
+        .map(|(_, fp)| fp.extension().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, vec!["gz", "log"]);
+    fs::remove_dir_all(&logs_dir).unwrap();
+}
+
+#[test]
+pub fn log_timezone_parsing_works() {
+    assert_eq!("local".parse::<LogTimezone>(), Ok(LogTimezone::Local));
+    let offset = chrono::FixedOffset::east_opt(2 * 3600 + 30 * 60).unwrap();
+    assert_eq!(
+        "+02:30".parse::<LogTimezone>(),
+        Ok(LogTimezone::Fixed(offset))
+    );
+    assert!("Europe/Paris".parse::<LogTimezone>().is_err());
+}
+
+#[test]
+pub fn log_file_names_are_safe() {
+    assert_eq!(file_name("##rust"), "##rust");
+    assert_eq!(file_name(".."), "%2E.");
+    assert_eq!(file_name("a/../b"), "a%2F..%2Fb");
+    assert_eq!(file_name("#{yyyy}%"), "#%7Byyyy%7D%25");
+    assert_eq!(file_name(""), "%00");
+
+    let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+    let path = dated_config().render(&file_name(".."), &file_name("../x"), date);
+    assert!(path
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_))));
+
+    let escaping = LogConfig {
+        path: String::from("../{channel}.log"),
+        ..LogConfig::default()
+    };
+    assert!(escaping.validate().is_err());
+}
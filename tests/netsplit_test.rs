@@ -0,0 +1,53 @@
+use libminirc::connection::CaseMapping;
+use libminirc::netsplit::*;
+use std::time::{Duration, Instant};
+
+#[test]
+pub fn detecting_netsplits_works() {
+    assert_eq!(
+        split_servers("irc.example.net hub.example.net"),
+        Some(("irc.example.net", "hub.example.net"))
+    );
+    assert_eq!(split_servers("*.net *.split"), Some(("*.net", "*.split")));
+    assert_eq!(split_servers("Ping timeout: 240 seconds"), None);
+    assert_eq!(split_servers("Quit: bye."), None);
+    assert_eq!(split_servers("Leaving"), None);
+}
+
+#[test]
+pub fn summing_up_netsplits_works() {
+    let channels = |c: &[&str]| c.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+    let mut split = Netsplit::new("libera", ("*.net", "*.split"));
+    split.add_quit("alice", &channels(&["#rust", "##foo"]));
+    split.add_quit("bob", &channels(&["#rust"]));
+    assert!(split.take_summaries(Instant::now()).is_empty());
+
+    let later = Instant::now() + Duration::from_secs(10);
+    assert_eq!(
+        split.take_summaries(later),
+        vec![
+            (
+                String::from("#rust"),
+                String::from("<-- Netsplit *.net <-> *.split, quit: alice, bob")
+            ),
+            (
+                String::from("##foo"),
+                String::from("<-- Netsplit *.net <-> *.split, quit: alice")
+            ),
+        ]
+    );
+    assert!(split.take_summaries(later).is_empty());
+
+    assert!(split.add_join("Alice", "#rust", CaseMapping::default()));
+    assert!(!split.add_join("carol", "#rust", CaseMapping::default()));
+    let later = Instant::now() + Duration::from_secs(10);
+    assert_eq!(
+        split.take_summaries(later),
+        vec![(
+            String::from("#rust"),
+            String::from("--> Netsplit *.net <-> *.split over, joined: Alice")
+        )]
+    );
+    assert!(!split.is_over(later));
+    assert!(split.is_over(later + Duration::from_secs(60 * 60)));
+}
@@ -0,0 +1,38 @@
+use chrono::NaiveTime;
+use libminirc::notify::*;
+use std::time::Duration;
+
+#[test]
+pub fn notifying_works() {
+    let hours = parse_quiet_hours("23:00-07:30").unwrap();
+    assert!(parse_quiet_hours("23:00").is_err());
+    assert!(parse_quiet_hours("23:00-25:00").is_err());
+
+    let method = NotifyMethod::Command(String::from("true"));
+    let mut notifier = Notifier::new(method, Duration::from_secs(60), Some(hours));
+    let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    assert!(notifier.is_quiet(time(23, 0)));
+    assert!(notifier.is_quiet(time(3, 0)));
+    assert!(!notifier.is_quiet(time(7, 30)));
+    assert!(!notifier.is_quiet(time(12, 0)));
+
+    let n = Notification {
+        network: "libera",
+        buffer: "#rust",
+        nick: "Ranmaru",
+        text: "Ran: hi",
+    };
+    assert!(!notifier.notify(&n, time(3, 0)).unwrap());
+    assert!(notifier.notify(&n, time(12, 0)).unwrap());
+    // Too soon after the last one
+    assert!(!notifier.notify(&n, time(12, 0)).unwrap());
+    assert!(notifier.take_escapes().is_empty());
+
+    let mut notifier = Notifier::new(NotifyMethod::Bell, Duration::default(), None);
+    assert!(notifier.notify(&n, time(12, 0)).unwrap());
+    assert_eq!(notifier.take_escapes(), vec!["\x07"]);
+    assert!(notifier.take_escapes().is_empty());
+
+    let mut notifier = Notifier::new(NotifyMethod::Off, Duration::default(), None);
+    assert!(!notifier.notify(&n, time(12, 0)).unwrap());
+}
@@ -0,0 +1,47 @@
+use libminirc::channel::*;
+use libminirc::command::{Command, Meta};
+use libminirc::connection::CaseMapping;
+use libminirc::logfile::LogConfig;
+use libminirc::store::Store;
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+pub fn renaming_query_works() {
+    let dir = env::temp_dir().join("minirc_rename_query_test");
+    let _ = fs::remove_dir_all(&dir);
+    let raw = ":Ranmaru!~ranmaru@host PRIVMSG Nobunaga :Hello!";
+    let (cmd, meta) = (Command::from(raw), Meta::from(raw));
+    let event = Event::new(&cmd, &meta).unwrap();
+
+    let logs = dir.join("freenode");
+    fs::create_dir_all(&logs).unwrap();
+    fs::write(logs.join("ran.txt"), "2020-06-01T12:00:00Z <Ran> Earlier\n").unwrap();
+
+    let log = LogConfig {
+        format: LogFormat::Both,
+        sqlite: true,
+        dir: Some(dir.clone()),
+        ..LogConfig::default()
+    };
+    let store = Arc::new(Store::open(&dir.join(STORE_FILE)).unwrap());
+    let mut chan = Channel::new("Ranmaru", "freenode", &log, CaseMapping::Rfc1459);
+    chan.set_store(store.clone());
+    chan.write(&event);
+    chan.rename("Ran").unwrap();
+    assert!(chan.is("ran"));
+    chan.write(&event);
+
+    // The old logs and stored events moved to the new name
+    assert!(!logs.join("ranmaru.txt").exists());
+    assert!(!logs.join("ranmaru.jsonl").exists());
+    let text = read_tail(&logs.join("ran.txt"), 5).unwrap();
+    assert_eq!(text.len(), 3);
+    assert!(text[0].ends_with("Earlier"));
+    assert_eq!(read_tail(&logs.join("ran.jsonl"), 5).unwrap().len(), 2);
+    let stored = store.tail("freenode", "ran", 5).unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[0].buffer, "Ran");
+    fs::remove_dir_all(&dir).unwrap();
+}
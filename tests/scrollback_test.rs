@@ -0,0 +1,75 @@
+use libminirc::scrollback::*;
+
+fn filled(n: usize) -> Scrollback {
+    let mut scrollback = Scrollback::new();
+    for i in 0..n {
+        scrollback.push(Line::new(&format!("line {}", i), Style::Normal));
+    }
+    scrollback
+}
+
+fn texts(view: &View) -> Vec<&str> {
+    view.rows.iter().map(|(row, _)| row.as_str()).collect()
+}
+
+fn last_row(scrollback: &Scrollback) -> String {
+    let view = scrollback.view(80, 3, "");
+    view.rows.last().unwrap().0.clone()
+}
+
+#[test]
+pub fn wrapping_works() {
+    assert_eq!(wrap("foo bar baz", 9), vec!["foo bar", "baz"]);
+    assert_eq!(wrap("abcdefghij", 5), vec!["abcd", "efgh", "ij"]);
+    assert_eq!(wrap("", 5), vec![""]);
+}
+
+#[test]
+pub fn scrolling_works() {
+    let mut scrollback = filled(10);
+    let view = scrollback.view(80, 3, "");
+    assert_eq!(texts(&view), vec!["line 7", "line 8", "line 9"]);
+    assert_eq!(view.more, 0);
+
+    scrollback.scroll(Scroll::Up(2));
+    let view = scrollback.view(80, 3, "");
+    assert_eq!(texts(&view), vec!["line 6", "line 7"]);
+    assert_eq!(view.more, 2);
+
+    // New lines don't move the view while scrolled up
+    scrollback.push(Line::new("line 10", Style::Normal));
+    let view = scrollback.view(80, 3, "");
+    assert_eq!(texts(&view), vec!["line 6", "line 7"]);
+    assert_eq!(view.more, 3);
+
+    scrollback.scroll(Scroll::Top);
+    let view = scrollback.view(80, 3, "");
+    assert_eq!(texts(&view), vec!["line 0", "line 1"]);
+    assert_eq!(view.more, 9);
+
+    scrollback.scroll(Scroll::Bottom);
+    assert!(!scrollback.is_scrolled());
+}
+
+#[test]
+pub fn searching_works() {
+    let mut scrollback = filled(30);
+    let regex = regex::Regex::new("(?i)LINE 2").unwrap();
+    assert_eq!(scrollback.search(regex), 11); // line 2, line 20..29
+
+    // Matches are visited from the newest, wrapping around
+    assert_eq!(scrollback.next_match(true), Some((1, 11)));
+    assert_eq!(last_row(&scrollback), "line 29");
+    assert_eq!(scrollback.next_match(true), Some((2, 11)));
+    assert_eq!(last_row(&scrollback), "line 28");
+    assert_eq!(scrollback.next_match(false), Some((1, 11)));
+    assert_eq!(scrollback.next_match(false), Some((11, 11)));
+    assert_eq!(last_row(&scrollback), "line 2");
+
+    // New lines are searched as they come in
+    scrollback.push(Line::new("line 200", Style::Normal));
+    assert_eq!(scrollback.next_match(false), Some((11, 12)));
+
+    scrollback.clear_search();
+    assert_eq!(scrollback.next_match(true), None);
+}
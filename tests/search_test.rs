@@ -0,0 +1,38 @@
+use libminirc::search::*;
+use std::env;
+use std::fs;
+
+#[test]
+pub fn building_regexes_works() {
+    let plain = build_regex("c++ (rust)", false).unwrap();
+    assert!(plain.is_match("I prefer C++ (Rust) actually"));
+    assert!(!plain.is_match("c rust"));
+
+    let regex = build_regex("^<\\w+> hi$", true).unwrap();
+    assert!(regex.is_match("<Ranmaru> HI"));
+    assert!(build_regex("(", true).is_err());
+}
+
+#[test]
+pub fn searching_logs_works() {
+    let dir = env::temp_dir().join("minirc_search_test");
+    fs::create_dir_all(dir.join("##foo")).unwrap();
+    let lines: Vec<_> = (0..30)
+        .map(|i| format!("2020-06-01T12:30:05Z <Ranmaru> line {}", i))
+        .collect();
+    fs::write(dir.join("##foo/2020-06-01.log"), lines.join("\n")).unwrap();
+    fs::write(dir.join("#bar.txt"), "2020-06-01T12:30:05Z <bob> LINE 7\n").unwrap();
+    fs::write(dir.join("minirc.db"), "line 7").unwrap();
+
+    let regex = build_regex("line 7", false).unwrap();
+    let hits = search_logs(&dir, &regex, false);
+    let labels: Vec<_> = hits.iter().map(|hit| hit.label.as_str()).collect();
+    assert_eq!(labels, vec!["##foo/2020-06-01.log", "#bar.txt"]);
+    assert!(search_logs(&dir, &regex, true).is_empty());
+
+    assert_eq!(hits[0].location(), "##foo/2020-06-01.log, line 8");
+    let (context, pos) = read_context(&hits[0], 3, None).unwrap();
+    assert_eq!(context.len(), 7);
+    assert_eq!(context[pos], "2020-06-01T12:30:05Z <Ranmaru> line 7");
+    fs::remove_dir_all(&dir).unwrap();
+}
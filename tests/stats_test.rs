@@ -0,0 +1,71 @@
+use libminirc::channel::JsonEvent;
+use libminirc::stats::*;
+use std::collections::BTreeMap;
+
+fn event(timestamp: &str, kind: &str, nick: &str, text: &str) -> JsonEvent {
+    JsonEvent {
+        timestamp: timestamp.to_owned(),
+        network: String::from("irc.example.org"),
+        buffer: String::from("#rust"),
+        kind: kind.to_owned(),
+        nick: Some(nick.to_owned()),
+        hostmask: None,
+        text: text.to_owned(),
+        tags: BTreeMap::new(),
+        msgid: None,
+    }
+}
+
+#[test]
+pub fn gathering_stats_works() {
+    let events = vec![
+        event(
+            "2020-06-01T12:30:05Z",
+            "privmsg",
+            "alice",
+            "Borrow checker, borrow!",
+        ),
+        event(
+            "2020-06-01T12:45:00Z",
+            "privmsg",
+            "bob",
+            "\u{1}ACTION borrows it\u{1}",
+        ),
+        event(
+            "2020-06-01T13:00:00Z",
+            "topic",
+            "bob",
+            "Rust: \u{2}borrowing\u{2}",
+        ),
+        event("2020-06-02T08:00:00Z", "privmsg", "alice", "later"),
+        event("2020-06-02T09:00:00Z", "join", "carol", ""),
+    ];
+    let stats = Stats::new(&events, "UTC".parse().unwrap());
+
+    assert_eq!(stats.messages(), 3);
+    assert_eq!(stats.hours[12], 2);
+    assert_eq!(stats.days.values().copied().collect::<Vec<_>>(), vec![2, 1]);
+
+    let users = stats.top_users();
+    assert_eq!(users[0].0, "alice");
+    assert_eq!((users[0].1.messages, users[0].1.words), (2, 4));
+    assert_eq!(users.len(), 3);
+    let bob = &stats.users["bob"];
+    assert_eq!(bob.last_seen.unwrap().to_string(), "2020-06-01 13:00:00");
+
+    assert_eq!(stats.top_words()[0], ("borrow", 2));
+    assert_eq!(
+        stats.longest[0],
+        (
+            String::from("alice"),
+            String::from("Borrow checker, borrow!")
+        )
+    );
+    assert_eq!(stats.topics[0].2, "Rust: \u{2}borrowing\u{2}");
+
+    let text = render_text(&stats, "#rust");
+    assert!(text.contains("3 messages from 3 nicks, 2020-06-01 to 2020-06-02"));
+    assert!(text.contains("2020-06-01 13:00 bob: Rust: borrowing"));
+    let html = render_html(&stats, "#rust & co");
+    assert!(html.contains("<h1>#rust &amp; co</h1>"));
+}
@@ -0,0 +1,74 @@
+use libminirc::channel::JsonEvent;
+use libminirc::search::{build_regex, read_context, search_store};
+use libminirc::store::Store;
+use std::env;
+use std::fs;
+
+fn event(buffer: &str, nick: &str, text: &str) -> JsonEvent {
+    JsonEvent {
+        timestamp: String::from("2020-06-01T12:30:05Z"),
+        network: String::from("freenode"),
+        buffer: buffer.to_owned(),
+        kind: String::from("privmsg"),
+        nick: Some(nick.to_owned()),
+        hostmask: None,
+        text: text.to_owned(),
+        tags: Default::default(),
+        msgid: None,
+    }
+}
+
+#[test]
+pub fn storing_and_searching_works() {
+    let fp = env::temp_dir().join("minirc_store_test.db");
+    let _ = fs::remove_file(&fp);
+    let store = Store::open(&fp).unwrap();
+    store
+        .insert(&event("##Foo", "Ranmaru", "Hello World!"), "##foo")
+        .unwrap();
+    store
+        .insert(&event("##foo", "alice", "hello again"), "##foo")
+        .unwrap();
+    store
+        .insert(&event("#bar", "bob", "Hello from bar"), "#bar")
+        .unwrap();
+
+    let tail = store.tail("freenode", "##foo", 1).unwrap();
+    assert_eq!(tail.len(), 1);
+    assert_eq!(
+        tail[0].to_log_line(),
+        "2020-06-01T12:30:05Z <alice> hello again"
+    );
+    assert_eq!(store.tail("freenode", "##foo", 10).unwrap().len(), 2);
+    assert!(store.tail("efnet", "##foo", 10).unwrap().is_empty());
+
+    let hits = store.search("freenode", None, "hello", 10).unwrap();
+    assert_eq!(hits.len(), 3);
+    let hits = store
+        .search("freenode", Some("##foo"), "world", 10)
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].1.buffer, "##Foo");
+
+    let (context, pos) = store.context(hits[0].0, 1).unwrap();
+    assert_eq!(context.len(), 2);
+    assert_eq!(context[pos].text, "Hello World!");
+    assert_eq!(context[pos + 1].text, "hello again");
+
+    let regex = build_regex("hello", false).unwrap();
+    let hits = search_store(&store, "freenode", &regex).unwrap();
+    assert_eq!(hits.len(), 3);
+    assert_eq!(hits[2].location(), "#bar");
+    // Parts of words and nicks are found, as in the logs
+    let regex = build_regex("wor", false).unwrap();
+    assert_eq!(search_store(&store, "freenode", &regex).unwrap().len(), 1);
+    let regex = build_regex("<bob>", false).unwrap();
+    assert_eq!(search_store(&store, "freenode", &regex).unwrap().len(), 1);
+    let regex = build_regex("^.* <(alice|bob)>", true).unwrap();
+    assert_eq!(search_store(&store, "freenode", &regex).unwrap().len(), 2);
+    assert!(search_store(&store, "efnet", &regex).unwrap().is_empty());
+    let (context, pos) = read_context(&hits[2], 3, Some(&store)).unwrap();
+    assert_eq!(context[pos], "2020-06-01T12:30:05Z <bob> Hello from bar");
+    drop(store);
+    fs::remove_file(&fp).unwrap();
+}